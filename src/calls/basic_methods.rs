@@ -1,52 +1,109 @@
+use crate::calls::language::choose_lang;
+use crate::calls::models::KnownUsers;
+use crate::consts::KNOWN_USERS_KEY;
+use crate::db::Db;
 use crate::errors::AppResult;
-use crate::models::{Command, MenuCommands};
-use crate::models::{MyDialogue, State};
-use crate::utils::make_keyboard;
+use crate::i18n::{Lang, tr};
+use crate::models::{Command, MyDialogue, State};
+use crate::utils::main_menu_keyboard;
 use teloxide::Bot;
 use teloxide::prelude::*;
 use teloxide::requests::Requester;
-use teloxide::utils::command::BotCommands;
 
-pub async fn help(bot: Bot, msg: Message) -> AppResult<()> {
-    bot.send_message(msg.chat.id, Command::descriptions().to_string())
+pub async fn help(bot: Bot, msg: Message, lang: Lang) -> AppResult<()> {
+    let commands = Command::localized(lang)
+        .iter()
+        .map(|command| format!("/{} — {}", command.command, command.description))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    bot.send_message(msg.chat.id, tr!(lang, "help", commands = commands))
         .await?;
     Ok(())
 }
 
-pub async fn start(bot: Bot, dialogue: MyDialogue, msg: Message) -> AppResult<()> {
-    let keyboard = make_keyboard(vec![
-        MenuCommands::CreateGiveaway.to_string(),
-        MenuCommands::CancelGiveaway.to_string(),
-        MenuCommands::GiveawayList.to_string(),
-        MenuCommands::AddGroupId.to_string(),
-    ]);
-
-    bot.send_message(
-        msg.chat.id,
-        "Привіт! Я бот для створення розіграшів! \n\n \
-                Тут ти можеш зробити розіграші для свого каналу"
-            .to_string(),
-    )
-    .reply_markup(keyboard.resize_keyboard())
-    .await?;
+pub async fn start(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let keyboard = main_menu_keyboard(lang);
+
+    // Remembered so owners can grant this user a role by @username
+    if let Some(user) = &msg.from
+        && let Some(username) = &user.username
+    {
+        let mut conn = pool.get().await?;
+        let mut known_users = KnownUsers::new(KNOWN_USERS_KEY.to_string(), &mut conn);
+        known_users
+            .insert(username.to_lowercase(), user.id.0, None)
+            .await?;
+    }
+
+    bot.send_message(msg.chat.id, tr!(lang, "start"))
+        .reply_markup(keyboard.resize_keyboard())
+        .await?;
 
     dialogue.update(State::StartedWindow).await?;
 
     Ok(())
 }
 
-pub async fn cancel(bot: Bot, dialogue: MyDialogue, msg: Message) -> AppResult<()> {
-    bot.send_message(msg.chat.id, "Cancelling the dialogue.")
+/// Sets the language of the bot for the user: `/language en`
+pub async fn language(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    code: String,
+    lang: Lang,
+) -> AppResult<()> {
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let chosen = match Lang::from_code(&code) {
+        Some(chosen) => chosen,
+        None => {
+            let languages = Lang::ALL
+                .into_iter()
+                .map(|lang| format!("{} — {}", lang.code(), lang.name()))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "language.usage",
+                    current = lang.name(),
+                    languages = languages
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    choose_lang(&pool, user_id, chosen).await?;
+
+    bot.send_message(msg.chat.id, tr!(chosen, "language.changed"))
+        .reply_markup(main_menu_keyboard(chosen).resize_keyboard())
         .await?;
+
+    dialogue.update(State::StartedWindow).await?;
+
+    Ok(())
+}
+
+pub async fn cancel(bot: Bot, dialogue: MyDialogue, msg: Message, lang: Lang) -> AppResult<()> {
+    bot.send_message(msg.chat.id, tr!(lang, "cancel")).await?;
     dialogue.exit().await?;
     Ok(())
 }
 
-pub async fn invalid_state(bot: Bot, msg: Message) -> AppResult<()> {
-    bot.send_message(
-        msg.chat.id,
-        "Я тебе не розумію, подивись будь-ласка на команду /help",
-    )
-    .await?;
+pub async fn invalid_state(bot: Bot, msg: Message, lang: Lang) -> AppResult<()> {
+    bot.send_message(msg.chat.id, tr!(lang, "invalid_state"))
+        .await?;
     Ok(())
 }
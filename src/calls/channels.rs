@@ -0,0 +1,123 @@
+use crate::calls::models::VerifiedChannels;
+use crate::consts::VERIFIED_CHANNELS_KEY;
+use crate::db::Db;
+use crate::errors::AppResult;
+use crate::i18n::Text;
+use teloxide::prelude::Requester;
+use teloxide::types::{ChatId, ChatMember, UserId};
+use teloxide::{ApiError, Bot, RequestError};
+
+/// Result of checking that a giveaway may be published to a channel on behalf of a user
+pub enum ChannelCheck {
+    /// Both the bot and the user may post, holds the channel as returned by `channel_name`
+    Verified(String),
+    NotFound,
+    BotNotAdmin,
+    UserNotAdmin,
+}
+
+impl ChannelCheck {
+    pub fn text(&self) -> Text {
+        match self {
+            ChannelCheck::Verified(channel) => {
+                Text::new("channel.verified").arg("channel", channel)
+            }
+            ChannelCheck::NotFound => Text::new("channel.not_found"),
+            ChannelCheck::BotNotAdmin => Text::new("channel.bot_not_admin"),
+            ChannelCheck::UserNotAdmin => Text::new("channel.user_not_admin"),
+        }
+    }
+}
+
+/// Checks via `get_chat_administrators` that both the bot and the user are admins
+/// who can post in the channel
+pub async fn check_channel_rights(
+    bot: &Bot,
+    channel: &str,
+    user_id: UserId,
+) -> AppResult<ChannelCheck> {
+    let chat = match bot.get_chat(channel.to_string()).await {
+        Ok(chat) => chat,
+        Err(RequestError::Api(ApiError::ChatNotFound)) => return Ok(ChannelCheck::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    let admins = match bot.get_chat_administrators(chat.id).await {
+        Ok(admins) => admins,
+        // Telegram hides the admin list of a channel from bots that are not admins there
+        Err(RequestError::Api(e)) => {
+            log::warn!("Cannot get administrators of {channel}: {e}");
+            return Ok(ChannelCheck::BotNotAdmin);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Only channels restrict posting to admins with the right, any group admin may post
+    let can_post = |admin: &ChatMember| !chat.is_channel() || admin.kind.can_post_messages();
+
+    let is_poster = |id: UserId| {
+        admins
+            .iter()
+            .any(|admin| admin.user.id == id && can_post(admin))
+    };
+
+    let me = bot.get_me().await?;
+
+    if !is_poster(me.id) {
+        return Ok(ChannelCheck::BotNotAdmin);
+    }
+
+    if !is_poster(user_id) {
+        return Ok(ChannelCheck::UserNotAdmin);
+    }
+
+    Ok(ChannelCheck::Verified(channel_name(
+        chat.id,
+        chat.username(),
+    )))
+}
+
+/// Runs `check_channel_rights` and remembers the channel for the user when it passes
+pub async fn verify_channel(
+    bot: &Bot,
+    pool: Db,
+    channel: &str,
+    user_id: UserId,
+) -> AppResult<ChannelCheck> {
+    let check = check_channel_rights(bot, channel, user_id).await?;
+
+    if let ChannelCheck::Verified(channel) = &check {
+        let mut conn = pool.get().await?;
+        let key = format!("{VERIFIED_CHANNELS_KEY}{}", user_id.0);
+        VerifiedChannels::new(key, &mut conn)
+            .insert(channel.clone())
+            .await?;
+    }
+
+    Ok(check)
+}
+
+/// Saved channels of the user, i.e. the ones that passed `verify_channel`, sorted by name
+pub async fn saved_channels(pool: Db, user_id: UserId) -> AppResult<Vec<String>> {
+    let mut conn = pool.get().await?;
+    let key = format!("{VERIFIED_CHANNELS_KEY}{}", user_id.0);
+
+    let mut channels = VerifiedChannels::new(key, &mut conn).members().await?;
+    channels.sort();
+
+    Ok(channels)
+}
+
+pub async fn remove_saved_channel(pool: Db, user_id: UserId, channel: String) -> AppResult<()> {
+    let mut conn = pool.get().await?;
+    let key = format!("{VERIFIED_CHANNELS_KEY}{}", user_id.0);
+
+    VerifiedChannels::new(key, &mut conn).remove(channel).await
+}
+
+/// Identifier to publish to: the `@username` of a public channel or the id of a private one
+pub fn channel_name(id: ChatId, username: Option<&str>) -> String {
+    username
+        .map(|username| format!("@{username}"))
+        .unwrap_or_else(|| id.to_string())
+}
@@ -0,0 +1,53 @@
+use crate::errors::{AppErrors, AppResult};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// # Provably fair draw
+/// - seed: 32 random bytes (hex), generated when the giveaway is published and kept secret
+/// - commitment: hex SHA-256 of the seed bytes, published in the channel post
+/// - ordering: Fisher-Yates shuffle of the participant snapshot (user ids in ascending order),
+///   the j-th random number is the first 8 bytes (big-endian u64) of SHA-256(seed || j),
+///   where j is a big-endian u64 counter, reduced modulo the remaining length
+///
+/// Winners are the first eligible entries of the ordering, rerolls take the next entry that
+/// has not won yet. Ineligible entries are published as skipped, so anyone with the seed,
+/// the snapshot and the skipped list can reproduce the result.
+pub fn generate_seed() -> String {
+    let mut seed = [0u8; 32];
+    rand::rng().fill_bytes(&mut seed);
+    hex::encode(seed)
+}
+
+pub fn commitment(seed: &str) -> AppResult<String> {
+    let seed = decode_seed(seed)?;
+    Ok(hex::encode(Sha256::digest(seed)))
+}
+
+pub fn shuffle(seed: &str, snapshot: &[u64]) -> AppResult<Vec<u64>> {
+    let seed = decode_seed(seed)?;
+    let mut ordering = snapshot.to_vec();
+
+    for (counter, i) in (1..ordering.len()).rev().enumerate() {
+        let mut hasher = Sha256::new();
+        hasher.update(&seed);
+        hasher.update((counter as u64).to_be_bytes());
+        let digest = hasher.finalize();
+
+        let mut number = [0u8; 8];
+        number.copy_from_slice(&digest[..8]);
+        let j = (u64::from_be_bytes(number) % (i as u64 + 1)) as usize;
+
+        ordering.swap(i, j);
+    }
+
+    Ok(ordering)
+}
+
+/// Next participant in the ordering who is not in `excluded`
+pub fn next_candidate(ordering: &[u64], excluded: &[u64]) -> Option<u64> {
+    ordering.iter().find(|id| !excluded.contains(id)).copied()
+}
+
+fn decode_seed(seed: &str) -> AppResult<Vec<u8>> {
+    hex::decode(seed).map_err(|e| AppErrors::StringError(format!("Invalid seed: {e}")))
+}
@@ -0,0 +1,207 @@
+use crate::calls::models::{Giveaway, RecentWinners};
+use crate::consts::RECENT_WINNERS_KEY;
+use crate::db::Db;
+use crate::errors::{AppErrors, AppResult};
+use crate::i18n::Text;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+use teloxide::prelude::Requester;
+use teloxide::types::{User, UserId};
+use teloxide::{ApiError, Bot, RequestError};
+
+/// Everything a rule may look at when a user presses the join button
+pub struct JoinContext<'a> {
+    pub bot: &'a Bot,
+    pub pool: &'a Db,
+    pub giveaway: &'a Giveaway,
+    pub user: &'a User,
+    pub participants: usize,
+}
+
+pub trait Eligibility {
+    /// `None` when the user passes the rule, otherwise the reason shown in the callback alert
+    async fn check(&self, ctx: &JoinContext<'_>) -> AppResult<Option<Text>>;
+}
+
+/// Subscription to the giveaway channel and partner channels, always enforced
+pub struct Subscription;
+
+impl Eligibility for Subscription {
+    async fn check(&self, ctx: &JoinContext<'_>) -> AppResult<Option<Text>> {
+        let missing = match missing_subscriptions(ctx.bot, ctx.giveaway, ctx.user.id).await {
+            Ok(missing) => missing,
+            Err(_) => return Ok(Some(Text::new("join.check_failed"))),
+        };
+
+        if missing.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            Text::new("join.subscribe").arg("channels", missing.join(", ")),
+        ))
+    }
+}
+
+/// Rules the owner can configure per giveaway
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EligibilityRule {
+    HasUsername,
+    NotBot,
+    PremiumOnly,
+    NoPremium,
+    NoRecentWins { days: u32 },
+    MaxParticipants { limit: usize },
+}
+
+impl EligibilityRule {
+    pub fn description(&self) -> Text {
+        match self {
+            EligibilityRule::HasUsername => Text::new("rule.username"),
+            EligibilityRule::NotBot => Text::new("rule.notbot"),
+            EligibilityRule::PremiumOnly => Text::new("rule.premium"),
+            EligibilityRule::NoPremium => Text::new("rule.nopremium"),
+            EligibilityRule::NoRecentWins { days } => Text::new("rule.recent").arg("days", days),
+            EligibilityRule::MaxParticipants { limit } => Text::new("rule.max").arg("limit", limit),
+        }
+    }
+
+    fn rejection(&self) -> Text {
+        Text::new("join.rule_failed").arg_text("rule", self.description())
+    }
+}
+
+impl Eligibility for EligibilityRule {
+    async fn check(&self, ctx: &JoinContext<'_>) -> AppResult<Option<Text>> {
+        let passed = match self {
+            EligibilityRule::HasUsername => ctx.user.username.is_some(),
+            EligibilityRule::NotBot => !ctx.user.is_bot,
+            EligibilityRule::PremiumOnly => ctx.user.is_premium,
+            EligibilityRule::NoPremium => !ctx.user.is_premium,
+            EligibilityRule::NoRecentWins { days } => !won_recently(ctx, *days).await?,
+            EligibilityRule::MaxParticipants { limit } => ctx.participants < *limit,
+        };
+
+        Ok((!passed).then(|| self.rejection()))
+    }
+}
+
+/// Rejection when `count`, the participant count right after a join, is over the limit.
+/// Concurrent joins all pass the check before any of them is stored
+pub fn over_limit(giveaway: &Giveaway, count: usize) -> Option<Text> {
+    giveaway
+        .rules
+        .iter()
+        .find(|rule| matches!(rule, EligibilityRule::MaxParticipants { limit } if count > *limit))
+        .map(EligibilityRule::rejection)
+}
+
+impl Display for EligibilityRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EligibilityRule::HasUsername => write!(f, "username"),
+            EligibilityRule::NotBot => write!(f, "notbot"),
+            EligibilityRule::PremiumOnly => write!(f, "premium"),
+            EligibilityRule::NoPremium => write!(f, "nopremium"),
+            EligibilityRule::NoRecentWins { days } => write!(f, "recent:{days}"),
+            EligibilityRule::MaxParticipants { limit } => write!(f, "max:{limit}"),
+        }
+    }
+}
+
+impl FromStr for EligibilityRule {
+    type Err = AppErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AppErrors::TextError(Text::new("rule.unknown").arg("rule", s));
+
+        match s.split_once(':') {
+            None => match s {
+                "username" => Ok(EligibilityRule::HasUsername),
+                "notbot" => Ok(EligibilityRule::NotBot),
+                "premium" => Ok(EligibilityRule::PremiumOnly),
+                "nopremium" => Ok(EligibilityRule::NoPremium),
+                _ => Err(invalid()),
+            },
+            Some(("recent", days)) => Ok(EligibilityRule::NoRecentWins {
+                days: days.parse().map_err(|_| invalid())?,
+            }),
+            Some(("max", limit)) => Ok(EligibilityRule::MaxParticipants {
+                limit: limit.parse().map_err(|_| invalid())?,
+            }),
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+/// Runs the subscription check and every configured rule, returning the first rejection
+pub async fn check_eligibility(ctx: &JoinContext<'_>) -> AppResult<Option<Text>> {
+    if let Some(rejection) = Subscription.check(ctx).await? {
+        return Ok(Some(rejection));
+    }
+
+    for rule in ctx.giveaway.rules.iter() {
+        if let Some(rejection) = rule.check(ctx).await? {
+            return Ok(Some(rejection));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Channels from `Giveaway::required_channels` the user is not subscribed to.
+/// Fails if Telegram couldn't tell, a deleted account counts as not subscribed
+pub async fn missing_subscriptions(
+    bot: &Bot,
+    giveaway: &Giveaway,
+    user_id: UserId,
+) -> AppResult<Vec<String>> {
+    let mut missing = vec![];
+
+    for channel in giveaway.required_channels() {
+        match bot.get_chat_member(channel.clone(), user_id).await {
+            Ok(member) if member.is_present() => {}
+            Ok(_) | Err(RequestError::Api(ApiError::UserNotFound)) => missing.push(channel),
+            Err(e) => {
+                log::warn!("Cannot check subscription of user {user_id} to {channel}: {e}");
+                return Err(e.into());
+            }
+        }
+    }
+
+    Ok(missing)
+}
+
+async fn won_recently(ctx: &JoinContext<'_>, days: u32) -> AppResult<bool> {
+    let owner_id = ctx.giveaway.get_owner().id.0;
+    let since = chrono::Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+
+    let mut conn = ctx.pool.get().await?;
+    let won_at = RecentWinners::new(format!("{RECENT_WINNERS_KEY}{owner_id}"), &mut conn)
+        .get(ctx.user.id.0)
+        .await?;
+
+    Ok(won_at.is_some_and(|won_at| won_at >= since))
+}
+
+/// Remembers the winners for `NoRecentWins`, a later win replaces an earlier one
+pub async fn record_wins(pool: &Db, owner_id: u64, winners: &[User], won_at: i64) -> AppResult<()> {
+    let mut conn = pool.get().await?;
+    let mut recent = RecentWinners::new(format!("{RECENT_WINNERS_KEY}{owner_id}"), &mut conn);
+
+    let ids: Vec<u64> = winners.iter().map(|winner| winner.id.0).collect();
+    let previous = recent.get_many(&ids).await?;
+
+    for (user_id, mut previous) in ids.into_iter().zip(previous) {
+        // A draw of another giveaway may record the same user meanwhile
+        while previous.is_none_or(|previous| previous < won_at) {
+            if recent.compare_and_set(user_id, previous, won_at).await? {
+                break;
+            }
+            previous = recent.get(user_id).await?;
+        }
+    }
+
+    Ok(())
+}
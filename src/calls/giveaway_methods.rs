@@ -1,58 +1,167 @@
-use crate::calls::models::{Giveaway, GiveawaysStorage};
-use crate::calls::write_participant;
-use crate::consts::{FILENAME, USER_GIVEAWAY_KEY};
+use crate::calls::channels::{
+    ChannelCheck, channel_name, remove_saved_channel, saved_channels, verify_channel,
+};
+use crate::calls::eligibility::EligibilityRule;
+use crate::calls::language::{chosen_lang, user_lang};
+use crate::calls::models::{
+    Giveaway, KnownUsers, Media, PendingAlbums, Prize, ScheduleStorage, ScheduledGiveaway,
+    SharedGiveaways,
+};
+use crate::calls::roles::{Access, Role, accessible_giveaways, giveaway_access};
+use crate::calls::status::GiveawayStatus;
+use crate::calls::{
+    cancel_publication, draw_and_announce, finalize_winners, find_giveaway, publish_giveaway,
+    refresh_closed_post, refresh_published_post, reroll_winner, send_with_media,
+    update_count_in_button, write_participant,
+};
+use crate::consts::{
+    ALBUM_TTL_SECS, DEFAULT_WINNERS_COUNT, END_SCHEDULE_KEY, FILENAME, KNOWN_USERS_KEY,
+    NOTICE_INTERVAL_MS, PENDING_ALBUMS_KEY, PUBLISH_SCHEDULE_KEY, SHARED_GIVEAWAYS_KEY,
+};
+use crate::db::Db;
 use crate::errors::{AppErrors, AppResult};
-use crate::models::{ListCommands, MenuCommands, MyDialogue, State};
-use crate::utils::make_keyboard;
-use bb8_redis::RedisConnectionManager;
-use bb8_redis::bb8::Pool;
+use crate::i18n::{Lang, Text, tr};
+use crate::models::{
+    GiveawayAction, ListCommands, MenuCommands, MyDialogue, State, pressed_button,
+};
+use crate::utils::{
+    format_timestamp, giveaways_keyboard, list_keyboard, main_menu_keyboard, make_keyboard,
+    mention, parse_short_id, parse_timestamp, short_id, status_filter_keyboard,
+};
 use std::fs::{File, remove_file};
 use std::io::Write;
 use std::str::FromStr;
-use teloxide::Bot;
-use teloxide::payloads::{AnswerCallbackQuerySetters, SendMessageSetters, SendPhotoSetters};
-use teloxide::prelude::{CallbackQuery, Message, Requester};
-use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+use std::time::Duration;
+use teloxide::payloads::{
+    AnswerCallbackQuerySetters, EditMessageReplyMarkupSetters, SendMessageSetters,
+};
+use teloxide::prelude::{CallbackQuery, ChatId, Message, Requester};
+use teloxide::types::{
+    ChatMemberUpdated, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, KeyboardRemove,
+    ParseMode, User, UserId,
+};
+use teloxide::utils::html;
+use teloxide::{Bot, RequestError};
 use uuid::Uuid;
 
 pub async fn started_window(
     bot: Bot,
     dialogue: MyDialogue,
     msg: Message,
-    pool: Pool<RedisConnectionManager>,
+    pool: Db,
+    lang: Lang,
+    menu: MenuCommands,
 ) -> AppResult<()> {
-    let menu = MenuCommands::from(msg.text().unwrap_or_default().to_string());
-
     match menu {
         MenuCommands::CreateGiveaway => {
-            bot.send_message(
+            // Without the menu a tapped button can't become the text of the post
+            bot.send_message(msg.chat.id, tr!(lang, "create.ask"))
+                .reply_markup(KeyboardRemove::new())
+                .await?;
+            dialogue.update(State::CreateGiveaway).await?;
+        }
+        MenuCommands::CancelGiveaway => {
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+            let has_giveaways = send_giveaway_picker(
+                &bot,
                 msg.chat.id,
-                "Відправ картинки з дописом щоб створити розіграш",
+                pool,
+                user_id,
+                GiveawayAction::Cancel,
+                tr!(lang, "cancel.pick"),
+                lang,
             )
             .await?;
-            dialogue.update(State::CreateGiveaway).await?;
+
+            if has_giveaways {
+                dialogue.update(State::CancelGiveaway).await?;
+            } else {
+                bot.send_message(msg.chat.id, tr!(lang, "cancel.none"))
+                    .await?;
+                dialogue.update(State::StartedWindow).await?;
+            }
         }
-        MenuCommands::CancelGiveaway => {
-            bot.send_message(msg.chat.id, "Виберіть ID розіграшу, який хочете скасувати")
-                .await?;
-            get_all_giveaways(bot, msg, pool).await?;
-            dialogue.update(State::CancelGiveaway).await?;
+        MenuCommands::PauseGiveaway => {
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+            let has_giveaways = send_giveaway_picker(
+                &bot,
+                msg.chat.id,
+                pool,
+                user_id,
+                GiveawayAction::Pause,
+                tr!(lang, "pause.pick"),
+                lang,
+            )
+            .await?;
+
+            if !has_giveaways {
+                bot.send_message(msg.chat.id, tr!(lang, "pause.none"))
+                    .await?;
+            }
+            dialogue.update(State::StartedWindow).await?;
+        }
+        MenuCommands::ResumeGiveaway => {
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+            let has_giveaways = send_giveaway_picker(
+                &bot,
+                msg.chat.id,
+                pool,
+                user_id,
+                GiveawayAction::Resume,
+                tr!(lang, "resume.pick"),
+                lang,
+            )
+            .await?;
+
+            if !has_giveaways {
+                bot.send_message(msg.chat.id, tr!(lang, "resume.none"))
+                    .await?;
+            }
+            dialogue.update(State::StartedWindow).await?;
+        }
+        MenuCommands::EditGiveaway => {
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+            let has_giveaways = send_giveaway_picker(
+                &bot,
+                msg.chat.id,
+                pool,
+                user_id,
+                GiveawayAction::Edit,
+                tr!(lang, "edit.pick"),
+                lang,
+            )
+            .await?;
+
+            if has_giveaways {
+                dialogue.update(State::EditGiveaway).await?;
+            } else {
+                bot.send_message(msg.chat.id, tr!(lang, "edit.none"))
+                    .await?;
+                dialogue.update(State::StartedWindow).await?;
+            }
         }
         MenuCommands::GiveawayList => {
-            let is_not_empty = get_all_giveaways(bot.clone(), msg.clone(), pool).await?;
+            let is_not_empty = get_all_giveaways(
+                bot.clone(),
+                msg.clone(),
+                pool.clone(),
+                &GiveawayStatus::LISTED,
+                lang,
+            )
+            .await?;
 
             if is_not_empty {
-                let keyboard = make_keyboard(vec![
-                    ListCommands::ShowParticipants.to_string(),
-                    ListCommands::Return.to_string(),
-                ]);
-
-                bot.send_message(
-                    msg.chat.id,
-                    "Якщо потрібен повний список учасників, натисни кнопку нижче",
-                )
-                .reply_markup(keyboard.resize_keyboard())
-                .await?;
+                bot.send_message(msg.chat.id, tr!(lang, "list.filter"))
+                    .reply_markup(status_filter_keyboard(lang))
+                    .await?;
+
+                bot.send_message(msg.chat.id, tr!(lang, "list.participants_hint"))
+                    .reply_markup(list_keyboard(lang).resize_keyboard())
+                    .await?;
 
                 dialogue.update(State::List).await?;
                 return Ok(());
@@ -61,273 +170,2001 @@ pub async fn started_window(
             dialogue.update(State::StartedWindow).await?;
         }
         MenuCommands::AddGroupId => {
-            bot.send_message(
-                msg.chat.id,
-                "Назву каналу та ID розіграшу через пробіл\n\
-                Наприклад: @channelname 1234567890",
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+            let channels = saved_channels(pool, user_id).await?;
+
+            let text = tr!(lang, "publish.ask");
+
+            if channels.is_empty() {
+                bot.send_message(msg.chat.id, text).await?;
+            } else {
+                bot.send_message(msg.chat.id, text)
+                    .reply_markup(make_keyboard(channels).resize_keyboard())
+                    .await?;
+            }
+
+            dialogue.update(State::AddGroupId).await?;
+        }
+        MenuCommands::SavedChannels => {
+            get_saved_channels(bot, msg, pool, lang).await?;
+            dialogue.update(State::SavedChannels).await?;
+        }
+        MenuCommands::SchedulePublication => {
+            bot.send_message(msg.chat.id, tr!(lang, "schedule.ask"))
+                .await?;
+            get_all_giveaways(
+                bot,
+                msg,
+                pool,
+                &[GiveawayStatus::Draft, GiveawayStatus::Scheduled],
+                lang,
             )
             .await?;
-            dialogue.update(State::AddGroupId).await?;
+            dialogue.update(State::SchedulePublication).await?;
         }
-        _ => {
+        MenuCommands::ScheduledPublications => {
+            get_scheduled_publications(bot, msg, pool, lang).await?;
             dialogue.update(State::StartedWindow).await?;
         }
+        MenuCommands::PartnerChannels => {
+            bot.send_message(msg.chat.id, tr!(lang, "partners.ask"))
+                .await?;
+            get_all_giveaways(bot, msg, pool, &GiveawayStatus::EDITABLE, lang).await?;
+            dialogue.update(State::SetPartnerChannels).await?;
+        }
+        MenuCommands::EligibilityRules => {
+            bot.send_message(msg.chat.id, tr!(lang, "rules.ask"))
+                .await?;
+            get_all_giveaways(bot, msg, pool, &GiveawayStatus::EDITABLE, lang).await?;
+            dialogue.update(State::SetRules).await?;
+        }
+        MenuCommands::EndGiveaway => {
+            bot.send_message(msg.chat.id, tr!(lang, "end.ask")).await?;
+            get_all_giveaways(
+                bot,
+                msg,
+                pool,
+                &[GiveawayStatus::Active, GiveawayStatus::Paused],
+                lang,
+            )
+            .await?;
+            dialogue.update(State::EndGiveaway).await?;
+        }
+        MenuCommands::GiveawayRoles => {
+            bot.send_message(msg.chat.id, tr!(lang, "roles.ask"))
+                .await?;
+            get_all_giveaways(bot, msg, pool, &GiveawayStatus::LISTED, lang).await?;
+            dialogue.update(State::ChooseRole).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Text that is not a button of the main menu, e.g. a label of an outdated keyboard
+pub async fn unknown_menu_button(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    lang: Lang,
+) -> AppResult<()> {
+    bot.send_message(msg.chat.id, tr!(lang, "menu.unknown"))
+        .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+        .await?;
+
+    dialogue.update(State::StartedWindow).await?;
+    Ok(())
+}
+
+pub async fn create_giveaway(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    log::info!("Creating giveaway by user {:?}", msg.from);
+
+    let media = Media::from_message(&msg);
+
+    // An album carries its caption on the first file
+    let text = match msg.caption().or(msg.text()) {
+        Some(text) => text,
+        None => {
+            bot.send_message(msg.chat.id, tr!(lang, "create.no_text"))
+                .await?;
+            dialogue.update(State::CreateGiveaway).await?;
+            return Ok(());
+        }
+    };
+
+    let id = Uuid::new_v4();
+
+    let giveaway = Giveaway::new(
+        text.to_string(),
+        media.into_iter().collect(),
+        msg.from.clone().expect("Cannot find from"),
+        lang,
+    );
+
+    if let Err(e) = giveaway.check_caption_len() {
+        bot.send_message(msg.chat.id, e.localized(lang)).await?;
+        dialogue.update(State::CreateGiveaway).await?;
+        return Ok(());
+    }
+
+    let mut conn = pool.get().await?;
+
+    // The other files of the album come as separate messages, see `add_album_item`
+    if let Some(group) = msg.media_group_id() {
+        let mut albums = PendingAlbums::new(PENDING_ALBUMS_KEY.to_string(), &mut conn);
+        albums
+            .insert(
+                group.to_string(),
+                id,
+                Some(Duration::from_secs(ALBUM_TTL_SECS)),
+            )
+            .await?;
+    }
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id.0;
+
+    pool.giveaways().create(user_id, id, giveaway).await?;
+
+    bot.send_message(msg.chat.id, tr!(lang, "create.done", id = id))
+        .await?;
+
+    bot.send_message(msg.chat.id, tr!(lang, "end_time.ask"))
+        .reply_markup(make_keyboard(vec![tr!(lang, "end_time.none")]).resize_keyboard())
+        .await?;
+
+    dialogue.update(State::SetEndTime { id }).await?;
+    Ok(())
+}
+
+/// Giveaway created from an earlier file of the album the message belongs to
+pub async fn pending_album(msg: Message, pool: Db) -> Option<Uuid> {
+    let group = msg.media_group_id()?;
+
+    let pending = async {
+        let mut conn = pool.get().await?;
+        PendingAlbums::new(PENDING_ALBUMS_KEY.to_string(), &mut conn)
+            .get(group.to_string())
+            .await
+    };
+
+    match pending.await {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("Cannot look up album {group}: {e}");
+            None
+        }
+    }
+}
+
+/// Adds the next file of an album to the giveaway created from its first file,
+/// whatever step of the dialogue the user is at by then
+pub async fn add_album_item(msg: Message, pool: Db, id: Uuid) -> AppResult<()> {
+    let media = match Media::from_message(&msg) {
+        Some(media) => media,
+        None => return Ok(()),
+    };
+
+    let owner_id = msg.from.clone().expect("Cannot get from field").id.0;
+
+    // Files of a large album can arrive over more than the initial TTL
+    if let Some(group) = msg.media_group_id() {
+        let mut conn = pool.get().await?;
+        PendingAlbums::new(PENDING_ALBUMS_KEY.to_string(), &mut conn)
+            .expire(group.to_string(), Duration::from_secs(ALBUM_TTL_SECS))
+            .await?;
+    }
+
+    if let Some(mut giveaway) = pool.giveaways().get(owner_id, id).await? {
+        giveaway.add_media(media);
+        pool.giveaways().update(owner_id, id, giveaway).await?;
+        log::info!("Album file added to giveaway {id}");
+    }
+
+    Ok(())
+}
+
+pub async fn set_end_time(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    id: Uuid,
+    lang: Lang,
+) -> AppResult<()> {
+    let text = msg.text().unwrap_or_default().trim();
+
+    if Text::new("end_time.none").matches(text) {
+        bot.send_message(msg.chat.id, tr!(lang, "end_time.unlimited"))
+            .await?;
+        ask_for_prizes(bot, msg.chat.id, lang).await?;
+        dialogue.update(State::SetPrizes { id }).await?;
+        return Ok(());
+    }
+
+    let args = text.split_whitespace().collect::<Vec<&str>>();
+
+    let ends_at = match args
+        .get(0..2)
+        .and_then(|date| parse_timestamp(&date.join(" ")))
+    {
+        Some(ends_at) => ends_at,
+        None => {
+            bot.send_message(msg.chat.id, tr!(lang, "date.invalid"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if ends_at <= chrono::Utc::now().timestamp() {
+        bot.send_message(msg.chat.id, tr!(lang, "end_time.past"))
+            .await?;
+        return Ok(());
+    }
+
+    let count = match args.get(2).map(|count| count.parse::<usize>()) {
+        None => DEFAULT_WINNERS_COUNT,
+        Some(Ok(count)) if count > 0 => count,
+        Some(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "winners_count.invalid"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    let mut conn = pool.get().await?;
+
+    if let Err(e) = giveaway.set_end_time(ends_at, count) {
+        bot.send_message(msg.chat.id, e.localized(lang))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    pool.giveaways().update(owner_id, id, giveaway).await?;
+
+    let mut schedule = ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn);
+    schedule
+        .insert(
+            ScheduledGiveaway {
+                owner: owner_id,
+                id,
+            },
+            ends_at,
+        )
+        .await?;
+
+    log::info!("Giveaway {id} scheduled to end at {ends_at}");
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "end_time.set",
+            date = format_timestamp(ends_at),
+            count = count
+        ),
+    )
+    .await?;
+
+    ask_for_prizes(bot, msg.chat.id, lang).await?;
+    dialogue.update(State::SetPrizes { id }).await?;
+    Ok(())
+}
+
+async fn ask_for_prizes(bot: Bot, chat_id: ChatId, lang: Lang) -> AppResult<()> {
+    bot.send_message(chat_id, tr!(lang, "prizes.ask"))
+        .reply_markup(make_keyboard(vec![tr!(lang, "skip")]).resize_keyboard())
+        .await?;
+    Ok(())
+}
+
+pub async fn set_prizes(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    id: Uuid,
+    lang: Lang,
+) -> AppResult<()> {
+    let text = msg.text().unwrap_or_default().trim();
+
+    if Text::new("skip").matches(text) {
+        bot.send_message(msg.chat.id, tr!(lang, "prizes.skipped"))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    let prizes = match text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(Prize::from_str)
+        .collect::<AppResult<Vec<Prize>>>()
+    {
+        Ok(prizes) if !prizes.is_empty() => prizes,
+        Ok(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "prizes.empty"))
+                .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, e.localized(lang)).await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    if let Err(e) = giveaway.set_prizes(prizes) {
+        bot.send_message(msg.chat.id, e.localized(lang))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
     }
+
+    pool.giveaways()
+        .update(owner_id, id, giveaway.clone())
+        .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "prizes.saved",
+            count = giveaway.winners_count,
+            prizes = giveaway.prizes_text(lang)
+        ),
+    )
+    .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+    .await?;
+
+    dialogue.update(State::StartedWindow).await?;
+    Ok(())
+}
+
+pub async fn add_group_id(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    log::info!("Adding group ID to giveaway...");
+
+    let id = match msg.text() {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.missing"))
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let args = id.split_whitespace().collect::<Vec<&str>>();
+
+    match args.as_slice() {
+        [channelname] if Uuid::from_str(channelname).is_err() => {
+            let channel = channelname.to_string();
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+            let has_giveaways = send_giveaway_picker(
+                &bot,
+                msg.chat.id,
+                pool,
+                user_id,
+                GiveawayAction::Publish,
+                tr!(lang, "publish.pick", channel = &channel),
+                lang,
+            )
+            .await?;
+
+            if !has_giveaways {
+                bot.send_message(msg.chat.id, tr!(lang, "publish.none"))
+                    .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+                    .await?;
+                dialogue.update(State::StartedWindow).await?;
+                return Ok(());
+            }
+
+            bot.send_message(msg.chat.id, tr!(lang, "menu.back"))
+                .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+                .await?;
+
+            dialogue.update(State::PickGiveaway { channel }).await?;
+            Ok(())
+        }
+        [id] => {
+            let id = match Uuid::from_str(id) {
+                Ok(id) => id,
+                Err(_) => {
+                    bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                        .await?;
+                    dialogue.update(State::StartedWindow).await?;
+                    return Ok(());
+                }
+            };
+
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+            let channels = saved_channels(pool, user_id).await?;
+
+            if channels.is_empty() {
+                bot.send_message(msg.chat.id, tr!(lang, "publish.no_channels"))
+                    .await?;
+                return Ok(());
+            }
+
+            bot.send_message(msg.chat.id, tr!(lang, "publish.pick_channel"))
+                .reply_markup(make_keyboard(channels).resize_keyboard())
+                .await?;
+
+            dialogue.update(State::PickChannel { id }).await?;
+            Ok(())
+        }
+        [channelname, id, ..] => {
+            let channelname = channelname.to_string();
+            let id = match Uuid::from_str(id) {
+                Ok(id) => id,
+                Err(_) => {
+                    bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                        .await?;
+                    dialogue.update(State::StartedWindow).await?;
+                    return Ok(());
+                }
+            };
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+            publish_to_channel(
+                bot,
+                dialogue,
+                pool,
+                user_id,
+                msg.chat.id,
+                channelname,
+                id,
+                lang,
+            )
+            .await
+        }
+        [] => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.missing"))
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            Ok(())
+        }
+    }
+}
+
+pub async fn pick_channel(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    id: Uuid,
+    lang: Lang,
+) -> AppResult<()> {
+    let channelname = msg.text().unwrap_or_default().trim().to_string();
+
+    if channelname.is_empty() {
+        bot.send_message(msg.chat.id, tr!(lang, "publish.channel_missing"))
+            .await?;
+        return Ok(());
+    }
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+    publish_to_channel(
+        bot,
+        dialogue,
+        pool,
+        user_id,
+        msg.chat.id,
+        channelname,
+        id,
+        lang,
+    )
+    .await
+}
+
+/// Publishes the giveaway once the channel passes `verify_channel` for the requesting user
+#[allow(clippy::too_many_arguments)]
+async fn publish_to_channel(
+    bot: Bot,
+    dialogue: MyDialogue,
+    pool: Db,
+    user_id: UserId,
+    chat_id: ChatId,
+    channelname: String,
+    id: Uuid,
+    lang: Lang,
+) -> AppResult<()> {
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, chat_id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    if !giveaway.status().is_unpublished() {
+        let text = Text::new("status.not_publishable").arg_text("status", giveaway.status().text());
+        bot.send_message(chat_id, text.render(lang))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    let channelname = match verify_channel(&bot, pool.clone(), &channelname, user_id).await? {
+        ChannelCheck::Verified(channel) => channel,
+        rejected => {
+            bot.send_message(chat_id, rejected.text().render(lang))
+                .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    publish_giveaway(bot.clone(), id, &mut giveaway, channelname).await?;
+
+    let mut conn = pool.get().await?;
+
+    pool.giveaways()
+        .update(owner_id, id, giveaway.clone())
+        .await?;
+
+    let mut schedule = ScheduleStorage::new(PUBLISH_SCHEDULE_KEY.to_string(), &mut conn);
+    schedule
+        .remove(ScheduledGiveaway {
+            owner: owner_id,
+            id,
+        })
+        .await?;
+
+    bot.send_message(
+        chat_id,
+        tr!(
+            lang,
+            "publish.published",
+            channel = &giveaway.group_id,
+            id = id
+        ),
+    )
+    .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+    .await?;
+
+    dialogue.update(State::StartedWindow).await?;
+
+    Ok(())
+}
+
+pub async fn schedule_publication(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let args = msg
+        .text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+
+    if args.len() < 4 {
+        bot.send_message(msg.chat.id, tr!(lang, "schedule.missing_args"))
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    let channelname = args[0].to_string();
+
+    let id = match Uuid::from_str(args[1]) {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let publish_at = match parse_timestamp(&args[2..4].join(" ")) {
+        Some(publish_at) if publish_at > chrono::Utc::now().timestamp() => publish_at,
+        Some(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "schedule.past"))
+                .await?;
+            return Ok(());
+        }
+        None => {
+            bot.send_message(msg.chat.id, tr!(lang, "date.invalid"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    if !giveaway.status().is_unpublished() {
+        let text = Text::new("status.not_schedulable").arg_text("status", giveaway.status().text());
+        bot.send_message(msg.chat.id, text.render(lang)).await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    let channelname = match verify_channel(&bot, pool.clone(), &channelname, user_id).await? {
+        ChannelCheck::Verified(channel) => channel,
+        rejected => {
+            bot.send_message(msg.chat.id, rejected.text().render(lang))
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let mut conn = pool.get().await?;
+
+    giveaway.schedule_publication(channelname.clone(), publish_at)?;
+
+    pool.giveaways().update(owner_id, id, giveaway).await?;
+
+    let mut schedule = ScheduleStorage::new(PUBLISH_SCHEDULE_KEY.to_string(), &mut conn);
+    schedule
+        .insert(
+            ScheduledGiveaway {
+                owner: owner_id,
+                id,
+            },
+            publish_at,
+        )
+        .await?;
+
+    log::info!("Giveaway {id} scheduled to be published in {channelname} at {publish_at}");
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "schedule.done",
+            id = id,
+            channel = channelname,
+            date = format_timestamp(publish_at)
+        ),
+    )
+    .await?;
+
+    dialogue.update(State::StartedWindow).await?;
+    Ok(())
+}
+
+pub async fn set_partner_channels(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let args = msg
+        .text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+
+    let id = match args.first().map(|id| Uuid::from_str(id)) {
+        Some(Ok(id)) => id,
+        _ => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let channels = args[1..]
+        .iter()
+        .map(|channel| channel.to_string())
+        .collect::<Vec<String>>();
+
+    if let Some(channel) = channels.iter().find(|channel| !channel.starts_with('@')) {
+        bot.send_message(
+            msg.chat.id,
+            tr!(lang, "partners.invalid", channel = channel),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    if let Err(e) = giveaway.set_partner_channels(channels.clone()) {
+        bot.send_message(msg.chat.id, e.localized(lang)).await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    pool.giveaways().update(owner_id, id, giveaway).await?;
+
+    let text = if channels.is_empty() {
+        tr!(lang, "partners.removed", id = id)
+    } else {
+        tr!(
+            lang,
+            "partners.set",
+            id = id,
+            channels = channels.join(", ")
+        )
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+
+    dialogue.update(State::StartedWindow).await?;
+    Ok(())
+}
+
+pub async fn set_rules(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let args = msg
+        .text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+
+    let id = match args.first().map(|id| Uuid::from_str(id)) {
+        Some(Ok(id)) => id,
+        _ => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let rules = match args[1..]
+        .iter()
+        .map(|rule| EligibilityRule::from_str(rule))
+        .collect::<AppResult<Vec<EligibilityRule>>>()
+    {
+        Ok(rules) => rules,
+        Err(e) => {
+            bot.send_message(msg.chat.id, e.localized(lang)).await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    if let Err(e) = giveaway.set_rules(rules.clone()) {
+        bot.send_message(msg.chat.id, e.localized(lang)).await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    pool.giveaways().update(owner_id, id, giveaway).await?;
+
+    let text = if rules.is_empty() {
+        tr!(lang, "rules.removed", id = id)
+    } else {
+        let rules = rules
+            .iter()
+            .map(|rule| format!("• {}", rule.description().render(lang)))
+            .collect::<Vec<String>>()
+            .join("\n");
+        tr!(lang, "rules.set", id = id, rules = rules)
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+
+    dialogue.update(State::StartedWindow).await?;
+    Ok(())
+}
+
+pub async fn choose_role(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let args = msg
+        .text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+
+    let id = match args.first().map(|id| Uuid::from_str(id)) {
+        Some(Ok(id)) => id,
+        _ => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let role = match args.get(1) {
+        Some(&"remove") => None,
+        Some(role) => match Role::from_str(role) {
+            Ok(role) => Some(role),
+            Err(e) => {
+                bot.send_message(msg.chat.id, e.localized(lang)).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            bot.send_message(msg.chat.id, tr!(lang, "roles.missing_role"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((_, giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Owner, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    let mut members = vec![format!(
+        "{} — {}",
+        mention(&giveaway.owner),
+        Role::Owner.text().render(lang)
+    )];
+    for (member, role) in giveaway.roles.iter() {
+        members.push(format!(
+            "{} — {}",
+            html::user_mention(UserId(*member), &member.to_string()),
+            role.text().render(lang)
+        ));
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(lang, "roles.members", id = id, members = members.join("\n")),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+
+    dialogue.update(State::GrantRole { id, role }).await?;
+    Ok(())
+}
+
+pub async fn grant_role(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    (id, role): (Uuid, Option<Role>),
+    lang: Lang,
+) -> AppResult<()> {
+    let target = match msg.forward_from_user() {
+        Some(user) => Some(user.id),
+        None => match msg.text().map(str::trim) {
+            Some(username) if username.starts_with('@') => {
+                let mut conn = pool.get().await?;
+                let mut known_users = KnownUsers::new(KNOWN_USERS_KEY.to_string(), &mut conn);
+                known_users
+                    .get(username.trim_start_matches('@').to_lowercase())
+                    .await?
+                    .map(UserId)
+            }
+            _ => None,
+        },
+    };
+
+    let target = match target {
+        Some(target) => target,
+        None => {
+            bot.send_message(msg.chat.id, tr!(lang, "roles.user_not_found"))
+                .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Owner, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    if giveaway.owner.id == target {
+        bot.send_message(msg.chat.id, tr!(lang, "roles.author"))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    giveaway.set_role(target, role);
+
+    pool.giveaways().update(owner_id, id, giveaway).await?;
+
+    let mut conn = pool.get().await?;
+
+    let mut shared = SharedGiveaways::new(format!("{SHARED_GIVEAWAYS_KEY}{}", target.0), &mut conn);
+
+    let (reply, notice) = match role {
+        Some(role) => {
+            shared.insert(id).await?;
+            (
+                Text::new("roles.granted").arg_text("role", role.text()),
+                Text::new("roles.granted_notice")
+                    .arg_text("role", role.text())
+                    .arg("id", id),
+            )
+        }
+        None => {
+            shared.remove(id).await?;
+            (
+                Text::new("roles.revoked"),
+                Text::new("roles.revoked_notice").arg("id", id),
+            )
+        }
+    };
+
+    // Telegram only tells the language of the user who sent the update, so for the target
+    // it is known only if they chose one with /language
+    let notice_lang = chosen_lang(&pool, target).await?.unwrap_or(lang);
+
+    log::info!("User {user_id} set role {role:?} of user {target} in giveaway {id}");
+
+    if let Err(e) = bot.send_message(target, notice.render(notice_lang)).await {
+        log::warn!("Cannot notify user {target} about the role change: {e}");
+    }
+
+    bot.send_message(msg.chat.id, reply.render(lang))
+        .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+        .await?;
+
+    dialogue.update(State::StartedWindow).await?;
+    Ok(())
+}
+
+/// Owner id and the giveaway if the user has at least the `required` role in it,
+/// otherwise tells them why with the main menu
+async fn granted_giveaway(
+    bot: &Bot,
+    chat_id: ChatId,
+    pool: &Db,
+    user_id: UserId,
+    id: Uuid,
+    required: Role,
+    lang: Lang,
+) -> AppResult<Option<(u64, Giveaway)>> {
+    match giveaway_access(pool.clone(), user_id, id, required).await? {
+        Access::Granted {
+            owner_id, giveaway, ..
+        } => Ok(Some((owner_id, *giveaway))),
+        denied => {
+            bot.send_message(chat_id, denied.text().render(lang))
+                .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+                .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Giveaways the user may pick for `action`, ordered so pages stay stable
+async fn giveaways_for(
+    pool: Db,
+    user_id: UserId,
+    action: GiveawayAction,
+) -> AppResult<Vec<(Uuid, Giveaway)>> {
+    let mut giveaways = accessible_giveaways(pool, user_id)
+        .await?
+        .into_iter()
+        .filter(|(_, role, giveaway)| match action {
+            GiveawayAction::Cancel => {
+                *role >= Role::Owner && giveaway.status().can_become(GiveawayStatus::Cancelled)
+            }
+            GiveawayAction::ShowParticipants => true,
+            GiveawayAction::Publish => *role >= Role::Editor && giveaway.status().is_unpublished(),
+            GiveawayAction::Pause => {
+                *role >= Role::Editor && giveaway.status() == GiveawayStatus::Active
+            }
+            GiveawayAction::Resume => {
+                *role >= Role::Editor && giveaway.status() == GiveawayStatus::Paused
+            }
+            GiveawayAction::Edit => *role >= Role::Editor && giveaway.status().is_editable(),
+        })
+        .map(|(id, _, giveaway)| (id, giveaway))
+        .collect::<Vec<_>>();
+
+    giveaways.sort_by_key(|(id, _)| *id);
+
+    Ok(giveaways)
+}
+
+/// Sends the first page of giveaways to pick for `action`, returns `false` if there are none
+async fn send_giveaway_picker(
+    bot: &Bot,
+    chat_id: ChatId,
+    pool: Db,
+    user_id: UserId,
+    action: GiveawayAction,
+    text: String,
+    lang: Lang,
+) -> AppResult<bool> {
+    let giveaways = giveaways_for(pool, user_id, action).await?;
+
+    if giveaways.is_empty() {
+        return Ok(false);
+    }
+
+    bot.send_message(chat_id, text)
+        .reply_markup(giveaways_keyboard(&giveaways, action, 0, lang))
+        .await?;
+
+    Ok(true)
+}
+
+fn saved_channels_keyboard(channels: &[String], lang: Lang) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(channels.iter().map(|channel| {
+        vec![InlineKeyboardButton::callback(
+            tr!(lang, "channels.remove", channel = channel),
+            format!("rc:{channel}"),
+        )]
+    }))
+}
+
+pub async fn get_saved_channels(bot: Bot, msg: Message, pool: Db, lang: Lang) -> AppResult<()> {
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+    let channels = saved_channels(pool, user_id).await?;
+
+    if channels.is_empty() {
+        bot.send_message(msg.chat.id, tr!(lang, "channels.none"))
+            .await?;
+    } else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(lang, "channels.list", channels = channels.join("\n")),
+        )
+        .reply_markup(saved_channels_keyboard(&channels, lang))
+        .await?;
+    }
+
+    bot.send_message(msg.chat.id, tr!(lang, "channels.add_hint"))
+        .await?;
+
+    Ok(())
+}
+
+/// Saves the channel of a forwarded post, other messages are handled as the main menu
+pub async fn save_forwarded_channel(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let chat = match msg.forward_from_chat() {
+        Some(chat) if chat.is_channel() => chat.clone(),
+        _ => {
+            return match pressed_button(msg.clone()) {
+                Some(menu) => started_window(bot, dialogue, msg, pool, lang, menu).await,
+                None => unknown_menu_button(bot, dialogue, msg, lang).await,
+            };
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let text =
+        match verify_channel(&bot, pool, &channel_name(chat.id, chat.username()), user_id).await? {
+            ChannelCheck::Verified(channel) => tr!(lang, "channels.saved", channel = channel),
+            rejected => rejected.text().render(lang),
+        };
+
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Saves the channel for the user who made the bot its admin
+pub async fn save_channel_membership(
+    bot: Bot,
+    update: ChatMemberUpdated,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    if !update.chat.is_channel() || !update.new_chat_member.is_administrator() {
+        return Ok(());
+    }
+
+    let channel = channel_name(update.chat.id, update.chat.username());
+
+    match verify_channel(&bot, pool, &channel, update.from.id).await? {
+        ChannelCheck::Verified(channel) => {
+            log::info!("Channel {channel} saved for user {}", update.from.id);
+
+            // The user may have never started the bot
+            if let Err(e) = bot
+                .send_message(
+                    update.from.id,
+                    tr!(lang, "channels.saved", channel = &channel),
+                )
+                .await
+            {
+                log::warn!("Cannot notify user {} about {channel}: {e}", update.from.id);
+            }
+        }
+        rejected => log::info!(
+            "Channel {channel} not saved for user {}: {}",
+            update.from.id,
+            rejected.text()
+        ),
+    }
+
+    Ok(())
+}
+
+pub async fn get_scheduled_publications(
+    bot: Bot,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let mut pending = accessible_giveaways(pool, user_id)
+        .await?
+        .into_iter()
+        .filter(|(_, role, _)| *role >= Role::Editor)
+        .filter_map(|(id, _, giveaway)| giveaway.publication.map(|publication| (id, publication)))
+        .collect::<Vec<_>>();
+
+    if pending.is_empty() {
+        bot.send_message(msg.chat.id, tr!(lang, "schedule.none"))
+            .await?;
+        return Ok(());
+    }
+
+    pending.sort_by_key(|(_, publication)| publication.at);
+
+    for (id, publication) in pending {
+        let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+            tr!(lang, "schedule.cancel"),
+            format!("cp:{id}"),
+        )]]);
+
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "schedule.item",
+                id = id,
+                channel = publication.channel,
+                date = format_timestamp(publication.at)
+            ),
+        )
+        .reply_markup(keyboard)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn cancel_giveaway(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let giveaway_id = msg.text().unwrap_or_default();
+    log::info!(
+        "Cancelling giveaway by user {:?}, giveaway id {}",
+        msg.from,
+        giveaway_id
+    );
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let id = match Uuid::from_str(giveaway_id.trim()) {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    confirm_cancellation(bot, dialogue, pool, user_id, msg.chat.id, id, lang).await
+}
+
+/// Asks the owner to confirm the cancellation, the answer comes back as a callback
+async fn confirm_cancellation(
+    bot: Bot,
+    dialogue: MyDialogue,
+    pool: Db,
+    user_id: UserId,
+    chat_id: ChatId,
+    id: Uuid,
+    lang: Lang,
+) -> AppResult<()> {
+    let Some((_, giveaway)) =
+        granted_giveaway(&bot, chat_id, &pool, user_id, id, Role::Owner, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    if let Err(e) = giveaway.check_transition(GiveawayStatus::Cancelled) {
+        bot.send_message(chat_id, e.localized(lang)).await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    let short = short_id(id);
+    let keyboard = InlineKeyboardMarkup::new(vec![
+        vec![InlineKeyboardButton::callback(
+            tr!(lang, "cancel.confirm"),
+            format!("cc:{short}"),
+        )],
+        vec![InlineKeyboardButton::callback(
+            tr!(lang, "cancel.confirm_notify"),
+            format!("cn:{short}"),
+        )],
+        vec![InlineKeyboardButton::callback(
+            tr!(lang, "cancel.keep"),
+            "ck",
+        )],
+    ]);
+
+    bot.send_message(
+        chat_id,
+        tr!(
+            lang,
+            "cancel.ask",
+            id = id,
+            text = giveaway.text.chars().take(100).collect::<String>()
+        ),
+    )
+    .reply_markup(keyboard)
+    .await?;
+
+    dialogue.update(State::StartedWindow).await?;
+    Ok(())
+}
+
+/// Marks the giveaway cancelled, closes its channel post and optionally notifies participants
+async fn cancel_giveaway_by_id(
+    bot: Bot,
+    pool: Db,
+    user_id: UserId,
+    chat_id: ChatId,
+    id: Uuid,
+    notify: bool,
+    lang: Lang,
+) -> AppResult<()> {
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, chat_id, &pool, user_id, id, Role::Owner, lang).await?
+    else {
+        return Ok(());
+    };
+
+    if let Err(e) = giveaway.cancel() {
+        bot.send_message(chat_id, e.localized(lang)).await?;
+        return Ok(());
+    }
+
+    pool.giveaways()
+        .update(owner_id, id, giveaway.clone())
+        .await?;
+
+    let mut conn = pool.get().await?;
+
+    for schedule_key in [END_SCHEDULE_KEY, PUBLISH_SCHEDULE_KEY] {
+        let mut schedule = ScheduleStorage::new(schedule_key.to_string(), &mut conn);
+        schedule
+            .remove(ScheduledGiveaway {
+                owner: owner_id,
+                id,
+            })
+            .await?;
+    }
+
+    if let Err(e) = refresh_closed_post(bot.clone(), &giveaway).await {
+        log::warn!("Cannot update the post of cancelled giveaway {id}: {e}");
+    }
+
+    let mut reply = tr!(lang, "cancel.done");
+
+    if notify {
+        let participants = pool.giveaways().participants(id).await?;
+        let notice = Text::new("cancel.notice")
+            .arg("text", giveaway.text.chars().take(100).collect::<String>());
+
+        // Large giveaways take a while at Telegram's rate, the owner gets the count when done
+        tokio::spawn(notify_cancellation(
+            bot.clone(),
+            pool,
+            chat_id,
+            participants,
+            notice,
+            lang,
+        ));
+        reply.push_str(&tr!(lang, "cancel.notifying"));
+    }
+
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Sends the cancellation notice to every participant at `NOTICE_INTERVAL_MS`,
+/// waiting out flood limits, then tells the owner how many were reached
+async fn notify_cancellation(
+    bot: Bot,
+    pool: Db,
+    chat_id: ChatId,
+    participants: Vec<User>,
+    notice: Text,
+    lang: Lang,
+) {
+    let mut notified = 0;
+    for participant in &participants {
+        let text = notice.render(user_lang(&pool, participant).await);
+
+        let mut sent = bot.send_message(participant.id, text.clone()).await;
+        if let Err(RequestError::RetryAfter(wait)) = sent {
+            tokio::time::sleep(wait.duration()).await;
+            sent = bot.send_message(participant.id, text).await;
+        }
+
+        match sent {
+            Ok(_) => notified += 1,
+            // Users who never started the bot can't be messaged
+            Err(e) => log::warn!("Cannot notify {} about cancellation: {e}", participant.id),
+        }
+
+        tokio::time::sleep(Duration::from_millis(NOTICE_INTERVAL_MS)).await;
+    }
+
+    let report = tr!(
+        lang,
+        "cancel.notified",
+        notified = notified,
+        total = participants.len()
+    );
+    if let Err(e) = bot.send_message(chat_id, report).await {
+        log::warn!("Cannot report cancellation notices to {chat_id}: {e}");
+    }
+}
+
+pub async fn edit_giveaway(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+) -> AppResult<()> {
+    let id = match Uuid::from_str(msg.text().unwrap_or_default().trim()) {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    ask_for_content(bot, dialogue, pool, user_id, msg.chat.id, id, lang).await
+}
+
+async fn ask_for_content(
+    bot: Bot,
+    dialogue: MyDialogue,
+    pool: Db,
+    user_id: UserId,
+    chat_id: ChatId,
+    id: Uuid,
+    lang: Lang,
+) -> AppResult<()> {
+    let Some((_, giveaway)) =
+        granted_giveaway(&bot, chat_id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    };
+
+    if let Err(e) = giveaway.check_editable() {
+        bot.send_message(chat_id, e.localized(lang))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
+
+    // The new text replaces the live post, a tapped menu button must not become it
+    bot.send_message(chat_id, tr!(lang, "edit.ask", text = giveaway.get_text()))
+        .reply_markup(KeyboardRemove::new())
+        .await?;
+
+    dialogue.update(State::EditContent { id }).await?;
     Ok(())
 }
 
-pub async fn create_giveaway(
+/// Replaces the text and/or photo and rewrites the channel post if it is published
+pub async fn edit_content(
     bot: Bot,
     dialogue: MyDialogue,
     msg: Message,
-    pool: Pool<RedisConnectionManager>,
+    pool: Db,
+    id: Uuid,
+    lang: Lang,
 ) -> AppResult<()> {
-    log::info!("Creating giveaway by user {:?}", msg.from);
+    let text = msg.caption().or(msg.text()).map(str::to_string);
+    let media = Media::from_message(&msg);
 
-    let photos = match msg.photo() {
-        Some(photos) => photos[0].clone(),
-        None => {
-            bot.send_message(msg.chat.id, "Треба надіслати фото")
-                .await?;
-            dialogue.update(State::CreateGiveaway).await?;
-            return Ok(());
-        }
-    };
+    if text.is_none() && media.is_none() {
+        bot.send_message(msg.chat.id, tr!(lang, "edit.empty"))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
 
-    let text = match msg.caption() {
-        Some(text) => text,
-        None => {
-            bot.send_message(msg.chat.id, "Треба надіслати текст разом з картинками")
-                .await?;
-            dialogue.update(State::CreateGiveaway).await?;
-            return Ok(());
-        }
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
     };
 
-    let id = Uuid::new_v4();
+    let media_changed = media.is_some();
 
-    let giveaway = Giveaway::new(
-        text.to_string(),
-        photos.file.id,
-        msg.from.clone().expect("Cannot find from"),
-    );
+    // A text message can't get a file, and Telegram can't edit an album into a single file
+    if media_changed && giveaway.get_message().is_some() && giveaway.has_text_post() {
+        bot.send_message(msg.chat.id, tr!(lang, "edit.media_locked"))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
 
-    let mut conn = pool.get().await?;
+    if let Err(e) = giveaway.set_content(text, media) {
+        bot.send_message(msg.chat.id, e.localized(lang))
+            .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+            .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
 
-    let user_id = msg.from.clone().expect("Cannot get from field").id.0;
+    pool.giveaways()
+        .update(owner_id, id, giveaway.clone())
+        .await?;
 
-    let key = format!("giveaway:{user_id}");
+    log::info!("Giveaway {id} edited by {user_id}");
 
-    let mut giveaway_list = GiveawaysStorage::new(key, &mut conn);
+    let count = pool.giveaways().participant_count(id).await?;
 
-    giveaway_list.insert(id, giveaway, None).await?;
+    let text = match refresh_published_post(bot.clone(), id, &giveaway, count, media_changed).await
+    {
+        Ok(()) => tr!(lang, "edit.done"),
+        Err(e) => {
+            log::warn!("Cannot edit the post of giveaway {id}: {e}");
+            tr!(lang, "edit.post_failed", error = e.localized(lang))
+        }
+    };
 
-    bot.send_message(msg.chat.id, format!("Розіграш створено, ID: {id}"))
+    bot.send_message(msg.chat.id, text)
+        .reply_markup(main_menu_keyboard(lang).resize_keyboard())
         .await?;
 
     dialogue.update(State::StartedWindow).await?;
     Ok(())
 }
 
-pub async fn add_group_id(
+/// Pauses or resumes joining and switches the button of the channel post accordingly
+async fn set_paused(
+    bot: Bot,
+    pool: Db,
+    user_id: UserId,
+    chat_id: ChatId,
+    id: Uuid,
+    paused: bool,
+    lang: Lang,
+) -> AppResult<()> {
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, chat_id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        return Ok(());
+    };
+
+    let status = if paused {
+        GiveawayStatus::Paused
+    } else {
+        GiveawayStatus::Active
+    };
+
+    if let Err(e) = giveaway.set_status(status) {
+        bot.send_message(chat_id, e.localized(lang)).await?;
+        return Ok(());
+    }
+
+    pool.giveaways()
+        .update(owner_id, id, giveaway.clone())
+        .await?;
+
+    let count = pool.giveaways().participant_count(id).await?;
+    update_count_in_button(bot.clone(), id, &giveaway, count).await;
+
+    log::info!("Giveaway {id} is now {status:?}");
+
+    let text = if paused {
+        tr!(lang, "pause.done", id = id)
+    } else {
+        tr!(lang, "resume.done", id = id)
+    };
+
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
+
+pub async fn end_giveaway(
     bot: Bot,
     dialogue: MyDialogue,
     msg: Message,
-    pool: Pool<RedisConnectionManager>,
+    pool: Db,
+    lang: Lang,
 ) -> AppResult<()> {
-    log::info!("Adding group ID to giveaway...");
+    log::info!("Ending giveaway by user {:?}", msg.from);
 
-    let id = match msg.text() {
-        Some(id) => id,
-        None => {
-            bot.send_message(msg.chat.id, "Треба надіслати ID розіграшу")
+    let args = msg
+        .text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+
+    let id = match args.first().map(|id| Uuid::from_str(id)) {
+        Some(Ok(id)) => id,
+        _ => {
+            bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
+                .await?;
+            dialogue.update(State::StartedWindow).await?;
+            return Ok(());
+        }
+    };
+
+    let count = match args.get(1).map(|count| count.parse::<usize>()) {
+        None => DEFAULT_WINNERS_COUNT,
+        Some(Ok(count)) if count > 0 => count,
+        Some(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "winners_count.invalid"))
                 .await?;
             dialogue.update(State::StartedWindow).await?;
             return Ok(());
         }
     };
 
-    let id = id.split_whitespace().collect::<Vec<&str>>();
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
 
-    if id.len() <= 1 {
-        bot.send_message(msg.chat.id, "Треба надіслати ID розіграшу")
-            .await?;
+    let Some((owner_id, giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
         dialogue.update(State::StartedWindow).await?;
         return Ok(());
     };
 
-    let channelname = id[0].to_string();
-
-    let id = Uuid::from_str(id[1])?;
-
-    let mut conn = pool.get().await?;
-
-    let from = msg.from.clone().expect("Cannot get from field").id.0;
-
-    let key = format!("{USER_GIVEAWAY_KEY}{from}");
-    let mut storage = GiveawaysStorage::new(key, &mut conn);
+    if let Err(e) = giveaway.check_transition(GiveawayStatus::Ended) {
+        bot.send_message(msg.chat.id, e.localized(lang)).await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
 
-    let giveaway = storage.get(id).await?;
+    let participants_count = pool.giveaways().participant_count(id).await?;
 
-    if giveaway.is_none() {
-        bot.send_message(msg.chat.id, "Не вдалось знайти розіграш з таким ID")
+    if participants_count == 0 {
+        bot.send_message(msg.chat.id, tr!(lang, "end.no_participants"))
             .await?;
         dialogue.update(State::StartedWindow).await?;
         return Ok(());
     }
 
-    let mut giveaway = giveaway.expect("Cannot get giveaway from field");
+    let count = giveaway.winners_to_draw(count);
+
+    if let Err(e) = draw_and_announce(
+        pool,
+        bot.clone(),
+        msg.chat.id,
+        owner_id,
+        id,
+        giveaway,
+        count,
+        lang,
+    )
+    .await
+    {
+        log::error!("Cannot end giveaway {id}: {e}");
+        bot.send_message(
+            msg.chat.id,
+            tr!(lang, "end.failed", error = e.localized(lang)),
+        )
+        .reply_markup(main_menu_keyboard(lang).resize_keyboard())
+        .await?;
+        dialogue.update(State::StartedWindow).await?;
+        return Ok(());
+    }
 
-    let photo = giveaway.get_photo();
+    dialogue.update(State::RerollOrEnd).await?;
+    Ok(())
+}
 
-    giveaway.add_group_id(channelname.clone());
+/// Text after a draw that is not a menu button, the menu buttons are routed to `started_window`
+pub async fn reroll_or_end(bot: Bot, msg: Message, lang: Lang) -> AppResult<()> {
+    bot.send_message(msg.chat.id, tr!(lang, "end.reroll_or_finish"))
+        .await?;
+    Ok(())
+}
 
-    let url = format!("j:{}:{}", msg.from.expect("Cannot get from field").id, id);
+pub async fn verify(bot: Bot, msg: Message, id: String, pool: Db, lang: Lang) -> AppResult<()> {
+    let id = match Uuid::from_str(id.trim()) {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "verify.usage"))
+                .await?;
+            return Ok(());
+        }
+    };
 
-    let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
-        "Взяти участь",
-        url,
-    )]]);
+    let giveaway = match find_giveaway(pool, id).await? {
+        Some(giveaway) => giveaway,
+        None => {
+            bot.send_message(msg.chat.id, Access::NotFound.text().render(lang))
+                .await?;
+            return Ok(());
+        }
+    };
 
-    let m = bot
-        .send_photo(channelname.clone(), photo)
-        .chat_id(channelname.clone())
-        .caption(giveaway.text.clone())
-        .reply_markup(keyboard)
-        .await?;
+    let (verification, seed, draw) = match (giveaway.verify()?, &giveaway.seed, &giveaway.draw) {
+        (Some(verification), Some(seed), Some(draw)) => (verification, seed, draw),
+        _ => {
+            bot.send_message(msg.chat.id, tr!(lang, "verify.not_drawn"))
+                .await?;
+            return Ok(());
+        }
+    };
 
-    giveaway.set_message(m);
+    let commitment = match verification.commitment_matches {
+        Some(true) => Text::new("verify.commitment_match"),
+        Some(false) => Text::new("verify.commitment_mismatch"),
+        None => Text::new("verify.commitment_missing"),
+    };
 
-    storage.insert(id, giveaway.clone(), None).await?;
+    let winners = if verification.winners_match {
+        Text::new("verify.winners_match")
+    } else {
+        Text::new("verify.winners_mismatch")
+    };
 
-    bot.send_message(
+    let report = Text::new("verify.report")
+        .arg("id", id)
+        .arg_text("commitment", commitment)
+        .arg_text("winners", winners)
+        .arg("seed", seed)
+        .arg("participants", draw.snapshot.len())
+        .arg("skipped", draw.skipped.len())
+        .arg("rerolls", giveaway.get_rerolls().len());
+
+    bot.send_message(msg.chat.id, report.render(lang)).await?;
+
+    let snapshot = draw
+        .snapshot
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let skipped = draw
+        .skipped
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+
+    bot.send_document(
         msg.chat.id,
-        format!(
-            "Розіграш створено в каналі {} з ID {}",
-            giveaway.group_id, id
-        ),
+        InputFile::memory(format!("seed: {seed}\nskipped: {skipped}\n{snapshot}\n"))
+            .file_name(format!("snapshot_{id}.txt")),
     )
     .await?;
 
-    dialogue.update(State::StartedWindow).await?;
+    Ok(())
+}
+
+pub async fn archive(bot: Bot, msg: Message, id: String, pool: Db, lang: Lang) -> AppResult<()> {
+    let id = match Uuid::from_str(id.trim()) {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, tr!(lang, "archive.usage"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Owner, lang).await?
+    else {
+        return Ok(());
+    };
+
+    if let Err(e) = giveaway.set_status(GiveawayStatus::Archived) {
+        bot.send_message(msg.chat.id, e.localized(lang)).await?;
+        return Ok(());
+    }
+
+    pool.giveaways().update(owner_id, id, giveaway).await?;
+
+    log::info!("Giveaway {id} archived by {user_id}");
+
+    bot.send_message(msg.chat.id, tr!(lang, "archive.done", id = id))
+        .await?;
 
     Ok(())
 }
 
-pub async fn cancel_giveaway(
+/// Sets the language of the channel post: `/postlang <ID> <uk|en>`, rewriting the post
+/// if it is already published
+pub async fn post_language(
     bot: Bot,
-    dialogue: MyDialogue,
     msg: Message,
-    pool: Pool<RedisConnectionManager>,
+    args: String,
+    pool: Db,
+    lang: Lang,
 ) -> AppResult<()> {
-    let giveaway_id = msg.text().unwrap_or_default();
-    log::info!(
-        "Cancelling giveaway by user {:?}, giveaway id {}",
-        msg.from,
-        giveaway_id
-    );
-    let mut conn = pool.get().await?;
+    let args = args.split_whitespace().collect::<Vec<&str>>();
+
+    let (id, post_lang) = match args.as_slice() {
+        [id, code] => match (Uuid::from_str(id), Lang::from_code(code)) {
+            (Ok(id), Some(post_lang)) => (id, post_lang),
+            _ => {
+                bot.send_message(msg.chat.id, tr!(lang, "post_lang.usage"))
+                    .await?;
+                return Ok(());
+            }
+        },
+        _ => {
+            bot.send_message(msg.chat.id, tr!(lang, "post_lang.usage"))
+                .await?;
+            return Ok(());
+        }
+    };
 
-    let key = format!(
-        "{}{}",
-        USER_GIVEAWAY_KEY,
-        msg.from.clone().expect("Cannot get from field").id.0
-    );
-    let mut storage = GiveawaysStorage::new(key, &mut conn);
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
 
-    storage
-        .remove(Uuid::from_str(giveaway_id).unwrap_or_default())
-        .await?;
+    let Some((owner_id, mut giveaway)) =
+        granted_giveaway(&bot, msg.chat.id, &pool, user_id, id, Role::Editor, lang).await?
+    else {
+        return Ok(());
+    };
 
-    bot.send_message(msg.chat.id, "Розіграш було закінчено")
+    if let Err(e) = giveaway.set_lang(post_lang) {
+        bot.send_message(msg.chat.id, e.localized(lang)).await?;
+        return Ok(());
+    }
+
+    pool.giveaways()
+        .update(owner_id, id, giveaway.clone())
         .await?;
 
-    dialogue.update(State::StartedWindow).await?;
+    log::info!("Post language of giveaway {id} set to {post_lang:?}");
+
+    let count = pool.giveaways().participant_count(id).await?;
+
+    let text = match refresh_published_post(bot.clone(), id, &giveaway, count, false).await {
+        Ok(()) => tr!(lang, "post_lang.done", id = id, language = post_lang.name()),
+        Err(e) => {
+            log::warn!("Cannot edit the post of giveaway {id}: {e}");
+            tr!(lang, "edit.post_failed", error = e.localized(lang))
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+
     Ok(())
 }
 
-fn get_giveaway_content(id: &Uuid, giveaway: &Giveaway) -> String {
+fn get_giveaway_content(
+    id: &Uuid,
+    giveaway: &Giveaway,
+    participants_count: usize,
+    lang: Lang,
+) -> String {
     let owner_id = giveaway.get_owner().id;
     let owner_name = giveaway
         .get_owner()
         .username
-        .unwrap_or("учасник".to_string());
+        .unwrap_or_else(|| tr!(lang, "giveaway.owner_fallback"));
     let mention = format!("<a href=\"tg://user?id={owner_id}\">{owner_name}</a>");
 
-    if giveaway.group_id.is_empty() {
-        format!(
-            "ID: {}\nВласник: {}\nТекст: {}\nУчасники: {}",
-            id,
-            mention,
-            giveaway.get_text(),
-            giveaway.get_participants().len(),
-        )
-    } else {
-        format!(
-            "ID: {}\nВласник: {}\nТекст: {}\nУчасники: {}\nГрупа: {}",
-            id,
-            mention,
-            giveaway.get_text(),
-            giveaway.get_participants().len(),
-            giveaway.group_id,
-        )
+    let mut content = tr!(
+        lang,
+        "giveaway.content",
+        id = id,
+        owner = mention,
+        status = giveaway.status().text().render(lang),
+        post_lang = giveaway.lang.name(),
+        text = giveaway.get_text(),
+        participants = participants_count,
+    );
+
+    if !giveaway.group_id.is_empty() {
+        let group = tr!(lang, "giveaway.group", group = &giveaway.group_id);
+        content.push_str(&format!("\n{group}"));
+    }
+
+    if !giveaway.prizes.is_empty() {
+        content.push_str(&format!("\n{}", giveaway.prizes_text(lang)));
+    }
+
+    if let Some(ends_at) = giveaway.ends_at {
+        let ends_at = tr!(lang, "giveaway.ends_at", date = format_timestamp(ends_at));
+        content.push_str(&format!("\n{ends_at}"));
     }
+
+    content
 }
 
 pub async fn get_all_giveaways(
     bot: Bot,
     msg: Message,
-    pool: Pool<RedisConnectionManager>,
+    pool: Db,
+    statuses: &[GiveawayStatus],
+    lang: Lang,
 ) -> AppResult<bool> {
-    let mut conn = pool.get().await?;
-    let key = format!(
-        "{}{}",
-        USER_GIVEAWAY_KEY,
-        msg.from.clone().expect("Cannot get from field").id.0
-    );
-    let mut storage = GiveawaysStorage::new(key, &mut conn);
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
 
-    let giveaways = storage.get_all().await?;
+    send_giveaways(bot, msg.chat.id, user_id, pool, statuses, lang).await
+}
 
-    if giveaways.is_empty() {
-        bot.send_message(msg.chat.id, "Немає активних розіграшів")
+/// Sends the giveaways with one of `statuses` grouped by status, returns `false` if there are none
+async fn send_giveaways(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: UserId,
+    pool: Db,
+    statuses: &[GiveawayStatus],
+    lang: Lang,
+) -> AppResult<bool> {
+    let giveaways = accessible_giveaways(pool.clone(), user_id).await?;
+
+    let mut found = false;
+
+    for status in statuses {
+        let group = giveaways
+            .iter()
+            .filter(|(_, _, giveaway)| giveaway.status() == *status)
+            .collect::<Vec<_>>();
+
+        if group.is_empty() {
+            continue;
+        }
+        found = true;
+
+        bot.send_message(
+            chat_id,
+            format!("{} ({})", status.group_title().render(lang), group.len()),
+        )
+        .await?;
+
+        for (id, role, giveaway) in group {
+            let participants_count = pool.giveaways().participant_count(*id).await?;
+            let mut text = get_giveaway_content(id, giveaway, participants_count, lang);
+            if giveaway.owner.id != user_id {
+                let role = tr!(lang, "giveaway.your_role", role = role.text().render(lang));
+                text.push_str(&format!("\n{role}"));
+            }
+            // Only the first file of an album, to keep the list short
+            let media = giveaway.media();
+            let preview = &media[..media.len().min(1)];
+            send_with_media(
+                &bot,
+                chat_id.into(),
+                preview,
+                text,
+                Some(ParseMode::Html),
+                None,
+            )
             .await?;
-        return Ok(false);
-    } else {
-        for (id, giveaway) in giveaways {
-            let photo = giveaway.get_photo().clone();
-            let text = get_giveaway_content(&id, &giveaway);
-            bot.send_photo(msg.chat.id, photo)
-                .caption(text)
-                .parse_mode(ParseMode::Html)
-                .await?;
         }
     }
 
-    Ok(true)
-}
+    if !found {
+        bot.send_message(chat_id, tr!(lang, "list.empty")).await?;
+    }
 
-pub async fn list(bot: Bot, dialogue: MyDialogue, msg: Message) -> AppResult<()> {
-    let menu = ListCommands::from(msg.text().unwrap_or_default().to_string());
+    Ok(found)
+}
 
+pub async fn list(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    pool: Db,
+    lang: Lang,
+    menu: ListCommands,
+) -> AppResult<()> {
     match menu {
         ListCommands::ShowParticipants => {
-            bot.send_message(
+            let user_id = msg.from.clone().expect("Cannot get from field").id;
+
+            send_giveaway_picker(
+                &bot,
                 msg.chat.id,
-                "Виберіть ID розіграшу, учасників якого хочете побачити",
+                pool,
+                user_id,
+                GiveawayAction::ShowParticipants,
+                tr!(lang, "participants.pick"),
+                lang,
             )
             .await?;
             dialogue.update(State::ShowParticipants).await?;
         }
         ListCommands::Return => {
-            let keyboard = make_keyboard(vec![
-                MenuCommands::CreateGiveaway.to_string(),
-                MenuCommands::CancelGiveaway.to_string(),
-                MenuCommands::GiveawayList.to_string(),
-                MenuCommands::AddGroupId.to_string(),
-            ]);
-
-            bot.send_message(msg.chat.id, "Повернення назад")
+            let keyboard = main_menu_keyboard(lang);
+
+            bot.send_message(msg.chat.id, tr!(lang, "list.returning"))
                 .reply_markup(keyboard.resize_keyboard())
                 .await?;
 
@@ -337,56 +2174,62 @@ pub async fn list(bot: Bot, dialogue: MyDialogue, msg: Message) -> AppResult<()>
     Ok(())
 }
 
+/// Text in the giveaway list that is not one of its buttons
+pub async fn unknown_list_button(bot: Bot, msg: Message, lang: Lang) -> AppResult<()> {
+    bot.send_message(msg.chat.id, tr!(lang, "menu.unknown"))
+        .reply_markup(list_keyboard(lang).resize_keyboard())
+        .await?;
+    Ok(())
+}
+
 pub async fn show_participants(
     bot: Bot,
     dialogue: MyDialogue,
     msg: Message,
-    pool: Pool<RedisConnectionManager>,
+    pool: Db,
+    lang: Lang,
 ) -> AppResult<()> {
     log::info!("Showing participants...");
 
-    let keyboard = make_keyboard(vec![
-        MenuCommands::CreateGiveaway.to_string(),
-        MenuCommands::CancelGiveaway.to_string(),
-        MenuCommands::GiveawayList.to_string(),
-        MenuCommands::AddGroupId.to_string(),
-    ]);
-
-    let mut conn = pool.get().await?;
-
-    let key = format!(
-        "{}{}",
-        USER_GIVEAWAY_KEY,
-        msg.from.clone().expect("Cannot get from field").id.0
-    );
-    let mut storage = GiveawaysStorage::new(key, &mut conn);
+    let user_id = msg.from.clone().expect("Cannot get from field").id;
 
     let id = msg.text().unwrap_or_default();
 
     let id = match Uuid::from_str(id) {
         Ok(id) => id,
         Err(_) => {
-            bot.send_message(msg.chat.id, "Невірний ID розіграшу")
+            bot.send_message(msg.chat.id, tr!(lang, "id.invalid"))
                 .await?;
 
             return Ok(());
         }
     };
 
-    let giveaway = storage.get(id).await?;
+    send_participants(bot, dialogue, pool, user_id, msg.chat.id, id, lang).await
+}
 
-    if giveaway.is_none() {
-        bot.send_message(msg.chat.id, "Невірний ID розіграшу")
-            .await?;
+async fn send_participants(
+    bot: Bot,
+    dialogue: MyDialogue,
+    pool: Db,
+    user_id: UserId,
+    chat_id: ChatId,
+    id: Uuid,
+    lang: Lang,
+) -> AppResult<()> {
+    let keyboard = main_menu_keyboard(lang);
+
+    if granted_giveaway(&bot, chat_id, &pool, user_id, id, Role::Viewer, lang)
+        .await?
+        .is_none()
+    {
         return Ok(());
     }
 
-    let giveaway = giveaway.expect("Cannot get giveaway from field");
-
-    let participants = giveaway.get_participants();
+    let participants = pool.giveaways().participants(id).await?;
 
     if participants.is_empty() {
-        bot.send_message(msg.chat.id, "Немає учасників")
+        bot.send_message(chat_id, tr!(lang, "end.no_participants"))
             .reply_markup(keyboard.resize_keyboard())
             .await?;
         dialogue.update(State::StartedWindow).await?;
@@ -406,14 +2249,14 @@ pub async fn show_participants(
     }
 
     for (i, line) in lines.iter().enumerate() {
-        write!(file, "{}. {} \n", i + 1, line)?;
+        writeln!(file, "{}. {} ", i + 1, line)?;
     }
 
-    bot.send_message(msg.chat.id, "Ось список учасників")
+    bot.send_message(chat_id, tr!(lang, "participants.list"))
         .reply_markup(keyboard.resize_keyboard())
         .await?;
 
-    bot.send_document(msg.chat.id, teloxide::types::InputFile::file(FILENAME))
+    bot.send_document(chat_id, teloxide::types::InputFile::file(FILENAME))
         .await?;
 
     remove_file(FILENAME)?;
@@ -424,48 +2267,213 @@ pub async fn show_participants(
 
 pub async fn handle_callback_from_button(
     bot: Bot,
+    dialogue: MyDialogue,
     q: CallbackQuery,
-    pool: Pool<RedisConnectionManager>,
+    pool: Db,
+    lang: Lang,
 ) -> AppResult<()> {
     if let Some(data) = &q.data {
-        if data.starts_with("j:") {
-            let parser_string = data.replace("j:", "");
+        if data.starts_with("p:") || data.starts_with("j:") {
             let user = q.from.clone();
 
             log::info!(
                 "Handling callback from button by callback query by user {:?}",
                 q.from
             );
-            log::info!("User data: {parser_string:?}");
+            log::info!("User data: {data:?}");
 
-            let mut parts = parser_string.splitn(3, ':');
+            let (uuid, legacy_owner) = parse_join_data(data)?;
 
-            let user_id_str = parts
-                .next()
-                .ok_or(AppErrors::StringError("Missing user_id".to_string()))?;
-
-            let uuid_str = parts
-                .next()
-                .ok_or(AppErrors::StringError("Missing uuid".to_string()))?;
+            let owner_id = match pool.giveaways().owner(uuid).await?.or(legacy_owner) {
+                Some(owner_id) => owner_id,
+                None => {
+                    bot.answer_callback_query(q.id)
+                        .show_alert(true)
+                        .text(tr!(lang, "giveaway.not_found"))
+                        .await?;
+                    return Ok(());
+                }
+            };
 
             log::info!("User {} clicked on the button", user.id);
 
-            write_participant(
-                pool.clone(),
-                bot.clone(),
-                Uuid::from_str(uuid_str)?,
-                user_id_str.to_string(),
-                user,
-                q,
-            )
-            .await?;
+            write_participant(pool.clone(), bot.clone(), uuid, owner_id, user, q, lang).await?;
+        } else if data.starts_with("rr:") {
+            let parser_string = data.replace("rr:", "");
+
+            let (uuid_str, index_str) = parser_string
+                .split_once(':')
+                .ok_or(AppErrors::StringError("Missing winner index".to_string()))?;
+
+            let index = index_str
+                .parse::<usize>()
+                .map_err(|e| AppErrors::StringError(e.to_string()))?;
+
+            reroll_winner(pool, bot, Uuid::from_str(uuid_str)?, index, q, lang).await?;
+        } else if data.starts_with("cp:") {
+            let uuid_str = data.replace("cp:", "");
+
+            cancel_publication(pool, bot, Uuid::from_str(&uuid_str)?, q, lang).await?;
+        } else if data.starts_with("gs:") {
+            let (action, id) = data
+                .replace("gs:", "")
+                .split_once(':')
+                .and_then(|(action, id)| GiveawayAction::from_code(action).zip(parse_short_id(id)))
+                .ok_or(AppErrors::StringError(
+                    "Invalid giveaway selection".to_string(),
+                ))?;
+
+            let channel = match (action, dialogue.get().await?) {
+                (GiveawayAction::Publish, Some(State::PickGiveaway { channel })) => Some(channel),
+                (GiveawayAction::Publish, _) => {
+                    bot.answer_callback_query(q.id)
+                        .text(tr!(lang, "picker.expired"))
+                        .show_alert(true)
+                        .await?;
+                    return Ok(());
+                }
+                _ => None,
+            };
+
+            bot.answer_callback_query(q.id.clone()).await?;
+
+            let chat_id = match &q.message {
+                Some(message) => {
+                    bot.edit_message_reply_markup(message.chat().id, message.id())
+                        .reply_markup(InlineKeyboardMarkup::default())
+                        .await?;
+                    message.chat().id
+                }
+                None => ChatId::from(q.from.id),
+            };
+
+            match (action, channel) {
+                (GiveawayAction::Cancel, _) => {
+                    confirm_cancellation(bot, dialogue, pool, q.from.id, chat_id, id, lang).await?;
+                }
+                (GiveawayAction::ShowParticipants, _) => {
+                    send_participants(bot, dialogue, pool, q.from.id, chat_id, id, lang).await?;
+                }
+                (GiveawayAction::Publish, Some(channel)) => {
+                    publish_to_channel(bot, dialogue, pool, q.from.id, chat_id, channel, id, lang)
+                        .await?;
+                }
+                (GiveawayAction::Publish, None) => {}
+                (GiveawayAction::Pause, _) => {
+                    set_paused(bot, pool, q.from.id, chat_id, id, true, lang).await?;
+                }
+                (GiveawayAction::Resume, _) => {
+                    set_paused(bot, pool, q.from.id, chat_id, id, false, lang).await?;
+                }
+                (GiveawayAction::Edit, _) => {
+                    ask_for_content(bot, dialogue, pool, q.from.id, chat_id, id, lang).await?;
+                }
+            }
+        } else if data.starts_with("gn:") {
+            let (action, page) = data
+                .replace("gn:", "")
+                .split_once(':')
+                .and_then(|(action, page)| {
+                    GiveawayAction::from_code(action).zip(page.parse::<usize>().ok())
+                })
+                .ok_or(AppErrors::StringError("Invalid giveaways page".to_string()))?;
+
+            bot.answer_callback_query(q.id.clone()).await?;
+
+            if let Some(message) = &q.message {
+                let giveaways = giveaways_for(pool, q.from.id, action).await?;
+                bot.edit_message_reply_markup(message.chat().id, message.id())
+                    .reply_markup(giveaways_keyboard(&giveaways, action, page, lang))
+                    .await?;
+            }
+        } else if data.starts_with("rc:") {
+            let channel = data.replace("rc:", "");
+
+            remove_saved_channel(pool.clone(), q.from.id, channel.clone()).await?;
+
+            bot.answer_callback_query(q.id.clone())
+                .text(tr!(lang, "channels.removed", channel = &channel))
+                .await?;
+
+            if let Some(message) = &q.message {
+                let channels = saved_channels(pool, q.from.id).await?;
+                bot.edit_message_reply_markup(message.chat().id, message.id())
+                    .reply_markup(saved_channels_keyboard(&channels, lang))
+                    .await?;
+            }
+        } else if data.starts_with("cc:") || data.starts_with("cn:") || data == "ck" {
+            bot.answer_callback_query(q.id.clone()).await?;
+
+            let chat_id = match &q.message {
+                Some(message) => {
+                    bot.edit_message_reply_markup(message.chat().id, message.id())
+                        .reply_markup(InlineKeyboardMarkup::default())
+                        .await?;
+                    message.chat().id
+                }
+                None => ChatId::from(q.from.id),
+            };
+
+            if data == "ck" {
+                bot.send_message(chat_id, tr!(lang, "cancel.kept")).await?;
+                return Ok(());
+            }
+
+            let id = parse_short_id(&data[3..])
+                .ok_or(AppErrors::StringError("Invalid giveaway id".to_string()))?;
+
+            let notify = data.starts_with("cn:");
+            cancel_giveaway_by_id(bot, pool, q.from.id, chat_id, id, notify, lang).await?;
+        } else if data.starts_with("gf:") {
+            let status = GiveawayStatus::from_code(&data.replace("gf:", ""))
+                .ok_or(AppErrors::StringError("Invalid status filter".to_string()))?;
+
+            bot.answer_callback_query(q.id.clone()).await?;
+
+            let chat_id = q
+                .message
+                .as_ref()
+                .map(|message| message.chat().id)
+                .unwrap_or(ChatId::from(q.from.id));
+
+            send_giveaways(bot, chat_id, q.from.id, pool, &[status], lang).await?;
+        } else if data.starts_with("rf:") {
+            let uuid_str = data.replace("rf:", "");
+
+            finalize_winners(pool, bot, dialogue, Uuid::from_str(&uuid_str)?, q, lang).await?;
         }
     } else {
         bot.answer_callback_query(q.id)
             .show_alert(true)
-            .text("Не вдалось знайти розіграш")
+            .text(tr!(lang, "giveaway.not_found"))
             .await?;
     }
 
     Ok(())
 }
+
+/// Parses the join button data: `p:<short id>` or the legacy `j:<owner id>:<uuid>`
+/// of posts published before the global index, optionally followed by `:<timestamp>`
+fn parse_join_data(data: &str) -> AppResult<(Uuid, Option<u64>)> {
+    if let Some(rest) = data.strip_prefix("p:") {
+        let id = rest.split(':').next().unwrap_or_default();
+        let uuid =
+            parse_short_id(id).ok_or(AppErrors::StringError("Invalid giveaway id".to_string()))?;
+
+        return Ok((uuid, None));
+    }
+
+    let mut parts = data.trim_start_matches("j:").splitn(3, ':');
+
+    let owner_id = parts
+        .next()
+        .ok_or(AppErrors::StringError("Missing user_id".to_string()))?
+        .parse::<u64>()
+        .map_err(|e| AppErrors::StringError(e.to_string()))?;
+
+    let uuid_str = parts
+        .next()
+        .ok_or(AppErrors::StringError("Missing uuid".to_string()))?;
+
+    Ok((Uuid::from_str(uuid_str)?, Some(owner_id)))
+}
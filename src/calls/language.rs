@@ -0,0 +1,41 @@
+use crate::calls::models::UserLanguages;
+use crate::consts::USER_LANGUAGES_KEY;
+use crate::db::Db;
+use crate::errors::AppResult;
+use crate::i18n::Lang;
+use teloxide::types::{Update, User, UserId};
+
+/// Language chosen by the user with /language, if any
+pub async fn chosen_lang(pool: &Db, user_id: UserId) -> AppResult<Option<Lang>> {
+    let mut conn = pool.get().await?;
+    UserLanguages::new(USER_LANGUAGES_KEY.to_string(), &mut conn)
+        .get(user_id.0)
+        .await
+}
+
+pub async fn choose_lang(pool: &Db, user_id: UserId, lang: Lang) -> AppResult<()> {
+    let mut conn = pool.get().await?;
+    UserLanguages::new(USER_LANGUAGES_KEY.to_string(), &mut conn)
+        .insert(user_id.0, lang, None)
+        .await
+}
+
+/// Language to talk to the user in: the chosen one or the one of their Telegram app
+pub async fn user_lang(pool: &Db, user: &User) -> Lang {
+    match chosen_lang(pool, user.id).await {
+        Ok(Some(lang)) => lang,
+        Ok(None) => Lang::from_telegram(user.language_code.as_deref()),
+        Err(e) => {
+            log::warn!("Cannot get the language of user {}: {e}", user.id);
+            Lang::from_telegram(user.language_code.as_deref())
+        }
+    }
+}
+
+/// Language of the user behind the update, injected into every handler
+pub async fn update_lang(update: Update, pool: Db) -> Lang {
+    match update.from() {
+        Some(user) => user_lang(&pool, user).await,
+        None => Lang::default(),
+    }
+}
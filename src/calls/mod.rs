@@ -1,74 +1,140 @@
-use crate::calls::models::{Giveaway, GiveawaysStorage};
-use crate::consts::USER_GIVEAWAY_KEY;
-use crate::errors::AppResult;
-use bb8_redis::RedisConnectionManager;
-use bb8_redis::bb8::Pool;
+use crate::calls::eligibility::{
+    JoinContext, check_eligibility, missing_subscriptions, over_limit, record_wins,
+};
+use crate::calls::models::{Giveaway, Media, MediaKind, ScheduleStorage, ScheduledGiveaway};
+use crate::calls::roles::{Access, Role, giveaway_access};
+use crate::calls::status::GiveawayStatus;
+use crate::consts::{
+    ANNOUNCE_RETRY_SECS, ANNOUNCE_SCHEDULE_KEY, END_SCHEDULE_KEY, PUBLISH_SCHEDULE_KEY,
+    USER_GIVEAWAY_KEY,
+};
+use crate::db::Db;
+use crate::errors::{AppErrors, AppResult};
+use crate::i18n::{Lang, Text, tr};
+use crate::models::{MyDialogue, State};
+use crate::utils::{mention, short_id};
+use redis::{AsyncCommands, AsyncIter};
 use teloxide::Bot;
-use teloxide::payloads::{AnswerCallbackQuerySetters, EditMessageReplyMarkupSetters};
-use teloxide::prelude::{CallbackQuery, Requester};
-use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, User};
+use teloxide::payloads::{
+    AnswerCallbackQuerySetters, EditMessageCaptionSetters, EditMessageMediaSetters,
+    EditMessageReplyMarkupSetters, EditMessageTextSetters, SendMessageSetters,
+};
+use teloxide::prelude::{CallbackQuery, ChatId, Message, Requester};
+use teloxide::requests::HasPayload;
+use teloxide::types::{
+    InlineKeyboardButton, InlineKeyboardMarkup, ParseMode, Recipient, ReplyMarkup, ReplyParameters,
+    User, UserId,
+};
+use teloxide::utils::html;
 use uuid::Uuid;
 
 pub mod basic_methods;
+pub mod channels;
+pub mod draw;
+pub mod eligibility;
 pub mod giveaway_methods;
+pub mod language;
 pub mod models;
+pub mod roles;
+pub mod status;
 pub mod types;
 
 pub async fn write_participant(
-    pool: Pool<RedisConnectionManager>,
+    pool: Db,
     bot: Bot,
     uuid: Uuid,
-    user_id: String,
+    owner_id: u64,
     from: User,
     q: CallbackQuery,
+    lang: Lang,
 ) -> AppResult<()> {
-    let mut conn = pool.get().await?;
-
-    let user_id: u64 = user_id.parse().expect("Cannot parse user_id from string");
+    let giveaways = pool.giveaways();
 
-    let key = format!("{USER_GIVEAWAY_KEY}{user_id}");
-    let mut storage = GiveawaysStorage::new(key, &mut conn);
-
-    let giveaway = storage.get(uuid).await?;
+    let giveaway = giveaways.get(owner_id, uuid).await?;
 
     if let Some(giveaway) = giveaway {
         log::info!("Giveaway {uuid} found");
-        if giveaway.check_user(from.clone()) {
-            log::info!("User {user_id} already take a part in this giveaway {uuid}");
+        if let Some(rejection) = giveaway.status().join_rejection() {
+            bot.answer_callback_query(q.id)
+                .text(rejection.render(lang))
+                .show_alert(true)
+                .await?;
+            return Ok(());
+        }
 
-            let timestamp = chrono::Utc::now().timestamp();
-            let id_for_callback = format!("j:{user_id}:{uuid}:{timestamp}");
+        if giveaways.is_participant(uuid, from.id).await? {
+            log::info!(
+                "User {} already take a part in this giveaway {uuid}",
+                from.id
+            );
 
             bot.answer_callback_query(q.id)
-                .text("Ти вже взяв участь у розіграші!".to_string())
+                .text(tr!(lang, "join.already"))
                 .show_alert(true)
                 .await?;
 
-            update_count_in_button(bot.clone(), id_for_callback, giveaway).await?;
+            let count = giveaways.participant_count(uuid).await?;
+            update_count_in_button(bot.clone(), uuid, &giveaway, count).await;
             return Ok(());
         }
 
-        let mut giveaway = giveaway.clone();
+        let ctx = JoinContext {
+            bot: &bot,
+            pool: &pool,
+            giveaway: &giveaway,
+            user: &from,
+            participants: giveaways.participant_count(uuid).await?,
+        };
 
-        giveaway.add_participant(from.clone());
+        if let Some(rejection) = check_eligibility(&ctx).await? {
+            log::info!(
+                "User {} rejected from giveaway {uuid}: {rejection}",
+                from.id
+            );
 
-        storage.insert(uuid, giveaway.clone(), None).await?;
+            bot.answer_callback_query(q.id)
+                .text(rejection.render(lang))
+                .show_alert(true)
+                .await?;
+            return Ok(());
+        }
 
-        log::info!(
-            "User {} successfully take a part in giveaway {}",
-            from.id,
-            uuid
-        );
+        let count = match giveaways.add_participant(uuid, &from).await? {
+            Some(count) => {
+                // The limit check above doesn't see concurrent joins, the count after the join does
+                if let Some(rejection) = over_limit(&giveaway, count) {
+                    giveaways.remove_participant(uuid, from.id).await?;
+                    log::info!("User {} joined giveaway {uuid} over the limit", from.id);
 
-        let timestamp = chrono::Utc::now().timestamp();
-        let id_for_callback = format!("j:{user_id}:{uuid}:{timestamp}");
+                    bot.answer_callback_query(q.id)
+                        .text(rejection.render(lang))
+                        .show_alert(true)
+                        .await?;
+                    return Ok(());
+                }
 
-        bot.answer_callback_query(q.id)
-            .text("Вітаю! Ти успішно взяв участь у розіграші!".to_string())
-            .show_alert(true)
-            .await?;
+                log::info!(
+                    "User {} successfully take a part in giveaway {}",
+                    from.id,
+                    uuid
+                );
+
+                bot.answer_callback_query(q.id)
+                    .text(tr!(lang, "join.success"))
+                    .show_alert(true)
+                    .await?;
+                count
+            }
+            None => {
+                bot.answer_callback_query(q.id)
+                    .text(tr!(lang, "join.already"))
+                    .show_alert(true)
+                    .await?;
+                giveaways.participant_count(uuid).await?
+            }
+        };
 
-        update_count_in_button(bot.clone(), id_for_callback, giveaway).await?;
+        update_count_in_button(bot.clone(), uuid, &giveaway, count).await;
     } else {
         log::error!("Giveaway {uuid} not found");
     }
@@ -76,28 +142,702 @@ pub async fn write_participant(
     Ok(())
 }
 
-pub async fn update_count_in_button(
+/// Join button of a published giveaway: the participant count, or a button that
+/// only explains that joining is paused
+///
+/// Both keep the join callback data, `write_participant` answers with the alert while paused.
+pub fn join_keyboard(uuid: Uuid, giveaway: &Giveaway, count: usize) -> InlineKeyboardMarkup {
+    let text = match giveaway.status() {
+        GiveawayStatus::Paused => tr!(giveaway.lang, "post.join_paused"),
+        _ => tr!(giveaway.lang, "post.join_count", count = count),
+    };
+
+    // The timestamp makes the markup differ from the current one, Telegram rejects no-op edits
+    let timestamp = chrono::Utc::now().timestamp();
+    let callback_data = format!("p:{}:{timestamp}", short_id(uuid));
+
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        text,
+        callback_data,
+    )]])
+}
+
+/// Shows the participant count, or that joining is paused, on the join button
+///
+/// Failures are only logged: the join is already stored and Telegram may rate limit
+/// edits of a popular post.
+pub async fn update_count_in_button(bot: Bot, uuid: Uuid, giveaway: &Giveaway, count: usize) {
+    let keyboard = join_keyboard(uuid, giveaway, count);
+
+    let message = match giveaway.get_message() {
+        Some(message) => message,
+        None => return,
+    };
+
+    if let Err(e) = bot
+        .edit_message_reply_markup(message.chat_id(), message.message_id())
+        .reply_markup(keyboard)
+        .await
+    {
+        log::warn!("Cannot update participant count of giveaway {uuid}: {e}");
+    }
+}
+
+/// Rewrites the published post after an edit, replacing the file too if `media_changed`
+///
+/// Only a post with a single file can get a new one, see `Giveaway::has_text_post`.
+pub async fn refresh_published_post(
     bot: Bot,
-    callback_data: String,
-    giveaway: Giveaway,
+    uuid: Uuid,
+    giveaway: &Giveaway,
+    count: usize,
+    media_changed: bool,
 ) -> AppResult<()> {
-    let count = giveaway.get_participants().len();
-    let text = format!("Взяти участь ({count})");
+    let message = match giveaway.get_message() {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+
+    let keyboard = join_keyboard(uuid, giveaway, count);
+
+    match giveaway.media().first() {
+        Some(media) if media_changed && !giveaway.has_text_post() => {
+            let media = media.input_media(Some(giveaway.caption()));
+            bot.edit_message_media(message.chat_id(), message.message_id(), media)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        _ => edit_post_text(&bot, giveaway, keyboard).await?,
+    }
+
+    Ok(())
+}
+
+/// Puts the current caption into the channel post, as its text or as the caption of its file
+async fn edit_post_text(
+    bot: &Bot,
+    giveaway: &Giveaway,
+    keyboard: InlineKeyboardMarkup,
+) -> AppResult<()> {
+    let message = match giveaway.get_message() {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+
+    if giveaway.has_text_post() {
+        bot.edit_message_text(message.chat_id(), message.message_id(), giveaway.caption())
+            .reply_markup(keyboard)
+            .await?;
+    } else {
+        bot.edit_message_caption(message.chat_id(), message.message_id())
+            .caption(giveaway.caption())
+            .reply_markup(keyboard)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Removes the join button from the channel post so nobody can join after the draw
+pub async fn close_join_button(bot: Bot, giveaway: &Giveaway) -> AppResult<()> {
+    if let Some(message) = giveaway.get_message() {
+        bot.edit_message_reply_markup(message.chat_id(), message.message_id())
+            .reply_markup(InlineKeyboardMarkup::default())
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Rewrites the channel post caption, e.g. with the cancellation notice, and removes the button
+pub async fn refresh_closed_post(bot: Bot, giveaway: &Giveaway) -> AppResult<()> {
+    edit_post_text(&bot, giveaway, InlineKeyboardMarkup::default()).await
+}
+
+/// Sends `text` with the files: as the caption of a single file, as a plain message without
+/// files, or as a message replying to the album since albums can't have inline keyboards.
+/// Returns the message carrying the text and the keyboard
+pub async fn send_with_media(
+    bot: &Bot,
+    chat_id: Recipient,
+    media: &[Media],
+    text: String,
+    parse_mode: Option<ParseMode>,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> AppResult<Message> {
+    let reply_markup = keyboard.map(ReplyMarkup::InlineKeyboard);
+
+    let message = match media {
+        [] => {
+            let mut request = bot.send_message(chat_id, text);
+            request.payload_mut().parse_mode = parse_mode;
+            request.payload_mut().reply_markup = reply_markup;
+            request.await?
+        }
+        [single] => match single.kind {
+            MediaKind::Photo => {
+                let mut request = bot.send_photo(chat_id, single.input_file());
+                request.payload_mut().caption = Some(text);
+                request.payload_mut().parse_mode = parse_mode;
+                request.payload_mut().reply_markup = reply_markup;
+                request.await?
+            }
+            MediaKind::Video => {
+                let mut request = bot.send_video(chat_id, single.input_file());
+                request.payload_mut().caption = Some(text);
+                request.payload_mut().parse_mode = parse_mode;
+                request.payload_mut().reply_markup = reply_markup;
+                request.await?
+            }
+            MediaKind::Animation => {
+                let mut request = bot.send_animation(chat_id, single.input_file());
+                request.payload_mut().caption = Some(text);
+                request.payload_mut().parse_mode = parse_mode;
+                request.payload_mut().reply_markup = reply_markup;
+                request.await?
+            }
+            MediaKind::Document => {
+                let mut request = bot.send_document(chat_id, single.input_file());
+                request.payload_mut().caption = Some(text);
+                request.payload_mut().parse_mode = parse_mode;
+                request.payload_mut().reply_markup = reply_markup;
+                request.await?
+            }
+        },
+        album => {
+            let items = album.iter().map(|media| media.input_media(None));
+            let sent = bot.send_media_group(chat_id.clone(), items).await?;
+
+            let mut request = bot.send_message(chat_id, text);
+            request.payload_mut().parse_mode = parse_mode;
+            request.payload_mut().reply_markup = reply_markup;
+            request.payload_mut().reply_parameters =
+                sent.first().map(|first| ReplyParameters::new(first.id));
+            request.await?
+        }
+    };
+
+    Ok(message)
+}
+
+/// Posts the giveaway with the join button to the channel and remembers the message
+pub async fn publish_giveaway(
+    bot: Bot,
+    uuid: Uuid,
+    giveaway: &mut Giveaway,
+    channel: String,
+) -> AppResult<Message> {
+    if !giveaway.status().is_unpublished() {
+        return Err(AppErrors::TextError(
+            Text::new("status.not_publishable").arg_text("status", giveaway.status().text()),
+        ));
+    }
+
+    giveaway.add_group_id(channel.clone());
+    giveaway.commit_seed()?;
 
     let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
-        text,
-        callback_data,
+        tr!(giveaway.lang, "post.join"),
+        format!("p:{}", short_id(uuid)),
     )]]);
 
-    let message = giveaway
-        .get_message()
-        .clone()
-        .expect("Cannot get message")
-        .clone();
+    let m = send_with_media(
+        &bot,
+        Recipient::from(channel),
+        &giveaway.media(),
+        giveaway.caption(),
+        None,
+        Some(keyboard),
+    )
+    .await?;
 
-    bot.edit_message_reply_markup(giveaway.group_id, message.id)
-        .reply_markup(keyboard)
+    giveaway.set_message(&m);
+    giveaway.cancel_publication();
+    giveaway.set_status(GiveawayStatus::Active)?;
+
+    log::info!("Giveaway {uuid} published in {}", giveaway.group_id);
+
+    Ok(m)
+}
+
+pub async fn cancel_publication(
+    pool: Db,
+    bot: Bot,
+    uuid: Uuid,
+    q: CallbackQuery,
+    lang: Lang,
+) -> AppResult<()> {
+    let Some((owner_id, mut giveaway)) =
+        granted_for_callback(&bot, &q, &pool, uuid, Role::Editor, lang).await?
+    else {
+        return Ok(());
+    };
+
+    if giveaway.cancel_publication().is_none() {
+        bot.answer_callback_query(q.id)
+            .text(tr!(lang, "publication.not_pending"))
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    pool.giveaways().update(owner_id, uuid, giveaway).await?;
+
+    let mut conn = pool.get().await?;
+    let mut schedule = ScheduleStorage::new(PUBLISH_SCHEDULE_KEY.to_string(), &mut conn);
+    schedule
+        .remove(ScheduledGiveaway {
+            owner: owner_id,
+            id: uuid,
+        })
+        .await?;
+
+    log::info!("Publication of giveaway {uuid} cancelled");
+
+    bot.answer_callback_query(q.id.clone())
+        .text(tr!(lang, "publication.cancelled"))
         .await?;
 
+    if let Some(message) = &q.message {
+        bot.edit_message_reply_markup(message.chat().id, message.id())
+            .reply_markup(InlineKeyboardMarkup::default())
+            .await?;
+    }
+
     Ok(())
 }
+
+/// Owner id and the giveaway if the user who pressed the button has at least
+/// the `required` role in it, otherwise tells them why in an alert
+async fn granted_for_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    pool: &Db,
+    uuid: Uuid,
+    required: Role,
+    lang: Lang,
+) -> AppResult<Option<(u64, Giveaway)>> {
+    match giveaway_access(pool.clone(), q.from.id, uuid, required).await? {
+        Access::Granted {
+            owner_id, giveaway, ..
+        } => Ok(Some((owner_id, *giveaway))),
+        denied => {
+            bot.answer_callback_query(q.id.clone())
+                .text(denied.text().render(lang))
+                .show_alert(true)
+                .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Draws winners, persists them and announces the result to `chat_id` and the channel
+#[allow(clippy::too_many_arguments)]
+pub async fn draw_and_announce(
+    pool: Db,
+    bot: Bot,
+    chat_id: ChatId,
+    owner_id: u64,
+    uuid: Uuid,
+    mut giveaway: Giveaway,
+    count: usize,
+    lang: Lang,
+) -> AppResult<()> {
+    giveaway.check_transition(GiveawayStatus::Ended)?;
+
+    let profiles = pool.giveaways().participants(uuid).await?;
+    let snapshot: Vec<u64> = profiles.iter().map(|user| user.id.0).collect();
+    let ordering = giveaway.shuffle_participants(&snapshot)?;
+
+    let mut winners = vec![];
+    let mut skipped = vec![];
+
+    for id in ordering {
+        if winners.len() >= count {
+            break;
+        }
+
+        // An unanswered check fails the draw, the scheduler runs it again later
+        if missing_subscriptions(&bot, &giveaway, UserId(id))
+            .await?
+            .is_empty()
+        {
+            winners.extend(profiles.iter().find(|user| user.id.0 == id).cloned());
+        } else {
+            log::info!("Participant {id} of giveaway {uuid} skipped, not subscribed");
+            skipped.push(id);
+        }
+    }
+
+    giveaway.record_draw(snapshot, winners, skipped)?;
+
+    // Queued before the draw is saved, so a saved draw always gets announced
+    queue_announcement(&pool, owner_id, uuid).await?;
+
+    pool.giveaways()
+        .update(owner_id, uuid, giveaway.clone())
+        .await?;
+
+    let ended_at = giveaway.ended_at.unwrap_or_default();
+    record_wins(&pool, owner_id, giveaway.get_winners(), ended_at).await?;
+
+    let mut conn = pool.get().await?;
+    let mut schedule = ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn);
+    schedule
+        .remove(ScheduledGiveaway {
+            owner: owner_id,
+            id: uuid,
+        })
+        .await?;
+
+    log::info!(
+        "Giveaway {uuid} ended with {} winners",
+        giveaway.get_winners().len()
+    );
+
+    if let Err(e) = close_join_button(bot.clone(), &giveaway).await {
+        log::warn!("Cannot close join button of giveaway {uuid}: {e}");
+    }
+
+    if let Err(e) = post_announcement(&pool, &bot, owner_id, uuid, &mut giveaway).await {
+        log::warn!("Cannot announce winners of giveaway {uuid}, will retry: {e}");
+    }
+
+    send_winners(bot.clone(), chat_id, uuid, &giveaway, lang).await?;
+
+    if !giveaway.get_winners().is_empty() {
+        bot.send_message(chat_id, tr!(lang, "winners.reroll_hint"))
+            .reply_markup(winners_keyboard(uuid, &giveaway, lang))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Winners list grouped by prize tier
+pub fn format_winners(giveaway: &Giveaway, lang: Lang) -> String {
+    if giveaway.get_winners().is_empty() {
+        return tr!(lang, "winners.none");
+    }
+
+    let mut lines = vec![];
+    let mut current_prize = None;
+
+    for (i, winner) in giveaway.get_winners().iter().enumerate() {
+        let prize = giveaway.prize_for(i);
+
+        if let Some(tier) = prize
+            && prize != current_prize
+        {
+            lines.push(format!("🏆 {}:", html::escape(&tier.title)));
+            current_prize = prize;
+        }
+
+        lines.push(format!("{}. {}", i + 1, mention(winner)));
+    }
+
+    lines.join("\n")
+}
+
+/// Heading, winners and fairness proof of the results announcement
+fn announcement(giveaway: &Giveaway, uuid: Uuid, lang: Lang) -> (String, String, String) {
+    let heading = if giveaway.get_rerolls().is_empty() {
+        tr!(lang, "winners.heading")
+    } else {
+        tr!(lang, "winners.heading_updated")
+    };
+
+    let winners = format_winners(giveaway, lang);
+
+    let fairness = match &giveaway.seed {
+        Some(seed) => {
+            let commitment = giveaway
+                .commitment
+                .clone()
+                .unwrap_or_else(|| tr!(lang, "winners.not_committed"));
+            tr!(
+                lang,
+                "winners.fairness",
+                seed = seed,
+                hash = commitment,
+                id = uuid
+            )
+        }
+        None => String::new(),
+    };
+
+    (
+        heading,
+        tr!(lang, "winners.list", winners = winners),
+        fairness,
+    )
+}
+
+/// Sends the list of winners to the owner in `lang`
+pub async fn send_winners(
+    bot: Bot,
+    owner_chat: ChatId,
+    uuid: Uuid,
+    giveaway: &Giveaway,
+    lang: Lang,
+) -> AppResult<()> {
+    let (heading, winners, fairness) = announcement(giveaway, uuid, lang);
+    bot.send_message(
+        owner_chat,
+        format!("{heading}\nID: {uuid}\n\n{winners}{fairness}"),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+
+    Ok(())
+}
+
+/// Adds the giveaway to the schedule the scheduler retries channel announcements from
+pub async fn queue_announcement(pool: &Db, owner_id: u64, uuid: Uuid) -> AppResult<()> {
+    let mut conn = pool.get().await?;
+    ScheduleStorage::new(ANNOUNCE_SCHEDULE_KEY.to_string(), &mut conn)
+        .insert(
+            ScheduledGiveaway {
+                owner: owner_id,
+                id: uuid,
+            },
+            chrono::Utc::now().timestamp() + ANNOUNCE_RETRY_SECS,
+        )
+        .await
+}
+
+/// Replies to the channel post with the list of winners in the giveaway language
+/// and takes the giveaway off the announcement schedule
+pub async fn post_announcement(
+    pool: &Db,
+    bot: &Bot,
+    owner_id: u64,
+    uuid: Uuid,
+    giveaway: &mut Giveaway,
+) -> AppResult<()> {
+    if let Some(message) = giveaway.get_message() {
+        let (heading, winners, fairness) = announcement(giveaway, uuid, giveaway.lang);
+        bot.send_message(
+            message.chat_id(),
+            format!("{heading}\n\n{winners}{fairness}"),
+        )
+        .reply_parameters(ReplyParameters::new(message.message_id()))
+        .parse_mode(ParseMode::Html)
+        .await?;
+    }
+
+    giveaway.announcement_pending = false;
+    pool.giveaways()
+        .update(owner_id, uuid, giveaway.clone())
+        .await?;
+
+    let mut conn = pool.get().await?;
+    ScheduleStorage::new(ANNOUNCE_SCHEDULE_KEY.to_string(), &mut conn)
+        .remove(ScheduledGiveaway {
+            owner: owner_id,
+            id: uuid,
+        })
+        .await
+}
+
+pub fn winners_keyboard(uuid: Uuid, giveaway: &Giveaway, lang: Lang) -> InlineKeyboardMarkup {
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = giveaway
+        .get_winners()
+        .iter()
+        .enumerate()
+        .map(|(i, winner)| {
+            let name = winner
+                .username
+                .clone()
+                .unwrap_or_else(|| winner.first_name.clone());
+            let label = match giveaway.prize_for(i) {
+                Some(prize) => format!("🔄 {}. {} ({})", i + 1, name, prize.title),
+                None => format!("🔄 {}. {}", i + 1, name),
+            };
+            vec![InlineKeyboardButton::callback(
+                label,
+                format!("rr:{uuid}:{i}"),
+            )]
+        })
+        .collect();
+
+    keyboard.push(vec![InlineKeyboardButton::callback(
+        tr!(lang, "winners.finalize"),
+        format!("rf:{uuid}"),
+    )]);
+
+    InlineKeyboardMarkup::new(keyboard)
+}
+
+pub async fn reroll_winner(
+    pool: Db,
+    bot: Bot,
+    uuid: Uuid,
+    index: usize,
+    q: CallbackQuery,
+    lang: Lang,
+) -> AppResult<()> {
+    let Some((owner_id, mut giveaway)) =
+        granted_for_callback(&bot, &q, &pool, uuid, Role::Editor, lang).await?
+    else {
+        return Ok(());
+    };
+
+    if giveaway.is_finalized() || giveaway.status() != GiveawayStatus::Ended {
+        bot.answer_callback_query(q.id)
+            .text(tr!(lang, "winners.already_final"))
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let mut replacement = None;
+    let mut skipped = vec![];
+
+    for id in giveaway.reroll_candidates()? {
+        let missing = match missing_subscriptions(&bot, &giveaway, UserId(id)).await {
+            Ok(missing) => missing,
+            Err(_) => {
+                bot.answer_callback_query(q.id)
+                    .text(tr!(lang, "winners.check_failed"))
+                    .show_alert(true)
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        if missing.is_empty() {
+            replacement = pool.giveaways().participant(uuid, id).await?;
+            break;
+        }
+        skipped.push(id);
+    }
+
+    let reroll = match replacement.and_then(|user| giveaway.replace_winner(index, user, skipped)) {
+        Some(reroll) => reroll.clone(),
+        None => {
+            bot.answer_callback_query(q.id)
+                .text(tr!(lang, "winners.no_replacement"))
+                .show_alert(true)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    pool.giveaways()
+        .update(owner_id, uuid, giveaway.clone())
+        .await?;
+
+    let ended_at = giveaway.ended_at.unwrap_or(reroll.at);
+    record_wins(
+        &pool,
+        owner_id,
+        std::slice::from_ref(&reroll.replacement),
+        ended_at,
+    )
+    .await?;
+
+    log::info!(
+        "Winner {} of giveaway {uuid} replaced by {}",
+        reroll.replaced.id,
+        reroll.replacement.id
+    );
+
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    if let Some(message) = &q.message {
+        bot.edit_message_reply_markup(message.chat().id, message.id())
+            .reply_markup(winners_keyboard(uuid, &giveaway, lang))
+            .await?;
+    }
+
+    bot.send_message(
+        q.from.id,
+        tr!(
+            lang,
+            "winners.replaced",
+            winner = mention(&reroll.replaced),
+            replacement = mention(&reroll.replacement)
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn finalize_winners(
+    pool: Db,
+    bot: Bot,
+    dialogue: MyDialogue,
+    uuid: Uuid,
+    q: CallbackQuery,
+    lang: Lang,
+) -> AppResult<()> {
+    let Some((owner_id, mut giveaway)) =
+        granted_for_callback(&bot, &q, &pool, uuid, Role::Editor, lang).await?
+    else {
+        return Ok(());
+    };
+
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    if let Some(message) = &q.message {
+        bot.edit_message_reply_markup(message.chat().id, message.id())
+            .reply_markup(InlineKeyboardMarkup::default())
+            .await?;
+    }
+
+    if !giveaway.is_finalized() {
+        giveaway.finalize();
+        pool.giveaways()
+            .update(owner_id, uuid, giveaway.clone())
+            .await?;
+
+        log::info!("Giveaway {uuid} finalized");
+
+        if giveaway.get_rerolls().is_empty() {
+            bot.send_message(q.from.id, tr!(lang, "winners.finalized", id = uuid))
+                .await?;
+        } else {
+            giveaway.announcement_pending = true;
+            queue_announcement(&pool, owner_id, uuid).await?;
+            pool.giveaways()
+                .update(owner_id, uuid, giveaway.clone())
+                .await?;
+
+            if let Err(e) = post_announcement(&pool, &bot, owner_id, uuid, &mut giveaway).await {
+                log::warn!("Cannot announce new winners of giveaway {uuid}, will retry: {e}");
+            }
+
+            send_winners(bot, ChatId::from(q.from.id), uuid, &giveaway, lang).await?;
+        }
+    }
+
+    dialogue.update(State::StartedWindow).await?;
+
+    Ok(())
+}
+
+/// Looks a giveaway up by UUID across all owners
+pub async fn find_giveaway(pool: Db, uuid: Uuid) -> AppResult<Option<Giveaway>> {
+    let owner_id = match pool.giveaways().owner(uuid).await? {
+        Some(owner_id) => owner_id,
+        None => return Ok(None),
+    };
+
+    pool.giveaways().get(owner_id, uuid).await
+}
+
+/// Keys of every owner's giveaway hash
+pub async fn giveaway_keys(pool: Db) -> AppResult<Vec<String>> {
+    let mut conn = pool.get().await?;
+
+    let mut keys: Vec<String> = vec![];
+    let mut iter: AsyncIter<String> = conn.scan_match(format!("{USER_GIVEAWAY_KEY}*")).await?;
+    while let Some(key) = iter.next_item().await {
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
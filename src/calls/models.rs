@@ -1,36 +1,325 @@
-use crate::calls::types::RHashMap;
-use redis::aio::MultiplexedConnection;
+use crate::calls::draw;
+use crate::calls::eligibility::EligibilityRule;
+use crate::calls::roles::Role;
+use crate::calls::status::GiveawayStatus;
+use crate::calls::types::{RHashMap, RSet, RSortedSet};
+use crate::consts::{
+    CAPTION_LIMIT, DEFAULT_WINNERS_COUNT, PARTICIPANT_PROFILES_KEY, PARTICIPANTS_KEY,
+    PROFILES_PAGE_SIZE, TEXT_LIMIT,
+};
+use crate::db::DbConnection;
+use crate::errors::{AppErrors, AppResult};
+use crate::i18n::{Lang, Text, tr};
+use crate::utils::format_timestamp;
 use redis::{FromRedisValue, ToRedisArgs};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use teloxide::prelude::Message;
-use teloxide::types::{InputFile, User, UserId};
+use std::str::FromStr;
+use teloxide::prelude::{ChatId, Message};
+use teloxide::types::{
+    InputFile, InputMedia, InputMediaAnimation, InputMediaDocument, InputMediaPhoto,
+    InputMediaVideo, MessageId, User, UserId,
+};
 use uuid::Uuid;
 
-pub type GiveawaysStorage<'a> = RHashMap<'a, MultiplexedConnection, String, Uuid, Giveaway>;
+pub type GiveawaysStorage<'a> = RHashMap<'a, DbConnection, String, Uuid, Giveaway>;
+/// Owner id of every giveaway, so a giveaway can be found by its UUID alone
+pub type GiveawayIndex<'a> = RHashMap<'a, DbConnection, String, Uuid, u64>;
+pub type ParticipantIds<'a> = RSet<'a, DbConnection, String, u64>;
+pub type ParticipantProfiles<'a> = RHashMap<'a, DbConnection, String, u64, User>;
+pub type ScheduleStorage<'a> = RSortedSet<'a, DbConnection, String, ScheduledGiveaway>;
+/// Giveaways of other users the user has a role in
+pub type SharedGiveaways<'a> = RSet<'a, DbConnection, String, Uuid>;
+/// User id by lowercase username of everyone who started the bot, to grant roles by @username
+pub type KnownUsers<'a> = RHashMap<'a, DbConnection, String, String, u64>;
+/// Channels where the user and the bot were confirmed to be admins with post rights
+pub type VerifiedChannels<'a> = RSet<'a, DbConnection, String, String>;
+/// Giveaway created from the first message of an album by the album's media group id
+pub type PendingAlbums<'a> = RHashMap<'a, DbConnection, String, String, Uuid>;
+/// Language chosen with /language by user id, overrides the Telegram `language_code`
+pub type UserLanguages<'a> = RHashMap<'a, DbConnection, String, u64, Lang>;
+/// When each user was last drawn as a winner of the owner's giveaways, by user id
+pub type RecentWinners<'a> = RHashMap<'a, DbConnection, String, u64, i64>;
 
-#[derive(Serialize, Deserialize)]
-pub struct GiveawaysList(HashMap<Uuid, Giveaway>);
+/// Member of a schedule sorted set, scored by the unix timestamp it is due at
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledGiveaway {
+    pub owner: u64,
+    pub id: Uuid,
+}
+
+/// Version written by this build, bump together with a step in `migrations`
+pub const GIVEAWAY_SCHEMA_VERSION: u32 = 5;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Giveaway {
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
     pub text: String,
     pub group_id: String,
-    pub message: Option<Message>,
-    pub photo: String,
+    #[serde(default)]
+    pub message_ref: Option<MessageRef>,
+    /// Full `teloxide::types::Message` stored by schema version 1, read only
+    #[serde(default, rename = "message", skip_serializing)]
+    legacy_message: Option<LegacyMessage>,
+    /// Single photo stored by schema version 4 and older, see `media`
+    #[serde(default, rename = "photo", skip_serializing)]
+    legacy_photo: String,
+    /// Files of the post, more than one is published as an album
+    #[serde(default)]
+    pub media: Vec<Media>,
     pub owner: User,
-    pub participants: Vec<User>,
+    /// Participants stored inline by schema version 2 and older, see `Participants`
+    #[serde(default, rename = "participants", skip_serializing)]
+    legacy_participants: Vec<User>,
+    #[serde(default)]
+    pub winners: Vec<User>,
+    #[serde(default)]
+    pub ended_at: Option<i64>,
+    #[serde(default)]
+    pub cancelled_at: Option<i64>,
+    #[serde(default)]
+    pub rerolls: Vec<Reroll>,
+    #[serde(default)]
+    pub finalized: bool,
+    /// The results are not yet posted in the channel, the scheduler retries the post
+    #[serde(default)]
+    pub announcement_pending: bool,
+    #[serde(default)]
+    pub seed: Option<String>,
+    #[serde(default)]
+    pub commitment: Option<String>,
+    #[serde(default)]
+    pub draw: Option<Draw>,
+    #[serde(default)]
+    pub ends_at: Option<i64>,
+    #[serde(default = "default_winners_count")]
+    pub winners_count: usize,
+    #[serde(default)]
+    pub publication: Option<Publication>,
+    #[serde(default)]
+    pub partner_channels: Vec<String>,
+    #[serde(default)]
+    pub rules: Vec<EligibilityRule>,
+    #[serde(default)]
+    pub prizes: Vec<Prize>,
+    /// Roles granted to other users, the creator is always the owner
+    #[serde(default)]
+    pub roles: HashMap<u64, Role>,
+    /// Missing before schema version 4, derived from the other fields until migrated
+    #[serde(default)]
+    status: Option<GiveawayStatus>,
+    /// Language of the channel post and the announcements there
+    #[serde(default)]
+    pub lang: Lang,
+}
+
+/// Prize tier, drawn in the order the tiers were added
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Prize {
+    pub title: String,
+    pub winners: usize,
+}
+
+impl FromStr for Prize {
+    type Err = AppErrors;
+
+    /// Parses `Title - count`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AppErrors::TextError(Text::new("prize.invalid").arg("prize", s.trim()));
+
+        let (title, winners) = s.rsplit_once('-').ok_or_else(invalid)?;
+        let title = title.trim();
+        let winners = winners.trim().parse::<usize>().map_err(|_| invalid())?;
+
+        if title.is_empty() || winners == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Prize {
+            title: title.to_string(),
+            winners,
+        })
+    }
+}
+
+/// Pending publication of a giveaway to a channel
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Publication {
+    pub channel: String,
+    pub at: i64,
+}
+
+fn default_winners_count() -> usize {
+    DEFAULT_WINNERS_COUNT
+}
+
+fn legacy_schema_version() -> u32 {
+    1
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MediaKind {
+    Photo,
+    Video,
+    Animation,
+    Document,
+}
+
+/// File attached to the giveaway post, referenced by its Telegram file id
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Media {
+    pub kind: MediaKind,
+    pub file_id: String,
+}
+
+impl Media {
+    /// The file attached to the message, the largest size of a photo
+    pub fn from_message(msg: &Message) -> Option<Media> {
+        let (kind, file_id) = if let Some(photos) = msg.photo() {
+            let photo = photos
+                .iter()
+                .max_by_key(|photo| photo.width * photo.height)?;
+            (MediaKind::Photo, photo.file.id.clone())
+        } else if let Some(video) = msg.video() {
+            (MediaKind::Video, video.file.id.clone())
+        } else if let Some(animation) = msg.animation() {
+            (MediaKind::Animation, animation.file.id.clone())
+        } else if let Some(document) = msg.document() {
+            (MediaKind::Document, document.file.id.clone())
+        } else {
+            return None;
+        };
+
+        Some(Media { kind, file_id })
+    }
+
+    pub fn input_file(&self) -> InputFile {
+        InputFile::file_id(&self.file_id)
+    }
+
+    /// The file as an album item or for `edit_message_media`
+    pub fn input_media(&self, caption: Option<String>) -> InputMedia {
+        let file = self.input_file();
+        let caption = caption.unwrap_or_default();
+
+        match self.kind {
+            MediaKind::Photo => InputMedia::Photo(InputMediaPhoto::new(file).caption(caption)),
+            MediaKind::Video => InputMedia::Video(InputMediaVideo::new(file).caption(caption)),
+            MediaKind::Animation => {
+                InputMedia::Animation(InputMediaAnimation::new(file).caption(caption))
+            }
+            MediaKind::Document => {
+                InputMedia::Document(InputMediaDocument::new(file).caption(caption))
+            }
+        }
+    }
+}
+
+/// Channel post of a published giveaway
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MessageRef {
+    pub chat_id: i64,
+    pub message_id: i32,
+    pub published_at: i64,
+}
+
+impl MessageRef {
+    pub fn chat_id(&self) -> ChatId {
+        ChatId(self.chat_id)
+    }
+
+    pub fn message_id(&self) -> MessageId {
+        MessageId(self.message_id)
+    }
+}
+
+impl From<&Message> for MessageRef {
+    fn from(message: &Message) -> Self {
+        MessageRef {
+            chat_id: message.chat.id.0,
+            message_id: message.id.0,
+            published_at: message.date.timestamp(),
+        }
+    }
+}
+
+/// The few fields of a serialized `Message` needed to build a `MessageRef`
+#[derive(Clone, Debug, Deserialize)]
+struct LegacyMessage {
+    message_id: i32,
+    date: i64,
+    chat: LegacyChat,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct LegacyChat {
+    id: i64,
+}
+
+impl From<&LegacyMessage> for MessageRef {
+    fn from(message: &LegacyMessage) -> Self {
+        MessageRef {
+            chat_id: message.chat.id,
+            message_id: message.message_id,
+            published_at: message.date,
+        }
+    }
+}
+
+/// Participant snapshot and initial winners recorded at draw time
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Draw {
+    pub snapshot: Vec<u64>,
+    pub winners: Vec<u64>,
+    /// Participants passed over because they were not eligible when their turn came
+    #[serde(default)]
+    pub skipped: Vec<u64>,
+}
+
+pub struct Verification {
+    /// `None` when no commitment was published before the draw
+    pub commitment_matches: Option<bool>,
+    pub winners_match: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Reroll {
+    pub replaced: User,
+    pub replacement: User,
+    pub at: i64,
 }
 
 impl Giveaway {
-    pub fn new(text: String, photo: String, owner: User) -> Self {
+    pub fn new(text: String, media: Vec<Media>, owner: User, lang: Lang) -> Self {
         Giveaway {
+            schema_version: GIVEAWAY_SCHEMA_VERSION,
             text,
             group_id: String::new(),
-            photo,
-            message: None,
+            legacy_photo: String::new(),
+            media,
+            message_ref: None,
+            legacy_message: None,
             owner,
-            participants: vec![],
+            legacy_participants: vec![],
+            winners: vec![],
+            ended_at: None,
+            cancelled_at: None,
+            rerolls: vec![],
+            finalized: false,
+            announcement_pending: false,
+            seed: None,
+            commitment: None,
+            draw: None,
+            ends_at: None,
+            winners_count: DEFAULT_WINNERS_COUNT,
+            publication: None,
+            partner_channels: vec![],
+            rules: vec![],
+            prizes: vec![],
+            roles: HashMap::new(),
+            status: Some(GiveawayStatus::Draft),
+            lang,
         }
     }
 
@@ -38,13 +327,100 @@ impl Giveaway {
         self.group_id = group_id;
     }
 
-    #[allow(dead_code)]
-    pub fn add_participant(&mut self, user: User) {
-        self.participants.push(user);
+    /// Replaces the text and/or all the media with a single file
+    pub fn set_content(&mut self, text: Option<String>, media: Option<Media>) -> AppResult<()> {
+        self.check_editable()?;
+        if let Some(text) = text {
+            self.text = text;
+        }
+        if let Some(media) = media {
+            self.legacy_photo.clear();
+            self.media = vec![media];
+        }
+        self.check_caption_len()
+    }
+
+    /// Adds the next file of an album that arrived after the giveaway was created
+    pub fn add_media(&mut self, media: Media) {
+        self.media.push(media);
+    }
+
+    pub fn set_partner_channels(&mut self, channels: Vec<String>) -> AppResult<()> {
+        self.check_editable()?;
+        self.partner_channels = channels;
+        Ok(())
+    }
+
+    pub fn set_prizes(&mut self, prizes: Vec<Prize>) -> AppResult<()> {
+        self.check_editable()?;
+        self.winners_count = prizes.iter().map(|prize| prize.winners).sum();
+        self.prizes = prizes;
+        self.check_caption_len()
+    }
+
+    /// Number of winners to draw, the prize tiers take precedence over `requested`
+    pub fn winners_to_draw(&self, requested: usize) -> usize {
+        if self.prizes.is_empty() {
+            requested
+        } else {
+            self.prizes.iter().map(|prize| prize.winners).sum()
+        }
+    }
+
+    /// Prize of the winner slot at `index`
+    pub fn prize_for(&self, index: usize) -> Option<&Prize> {
+        let mut slots = 0;
+        self.prizes.iter().find(|prize| {
+            slots += prize.winners;
+            index < slots
+        })
+    }
+
+    pub fn prizes_text(&self, lang: Lang) -> String {
+        let prizes = self
+            .prizes
+            .iter()
+            .map(|prize| format!("🏆 {} × {}", prize.title, prize.winners))
+            .collect::<Vec<String>>()
+            .join("\n");
+        format!("{}\n{prizes}", tr!(lang, "post.prizes"))
+    }
+
+    pub fn set_lang(&mut self, lang: Lang) -> AppResult<()> {
+        self.check_editable()?;
+        self.lang = lang;
+        self.check_caption_len()
+    }
+
+    pub fn set_rules(&mut self, rules: Vec<EligibilityRule>) -> AppResult<()> {
+        self.check_editable()?;
+        self.rules = rules;
+        self.check_caption_len()
+    }
+
+    /// Channels a participant has to be subscribed to
+    pub fn required_channels(&self) -> Vec<String> {
+        let mut channels = vec![];
+        if !self.group_id.is_empty() {
+            channels.push(self.group_id.clone());
+        }
+        channels.extend(self.partner_channels.iter().cloned());
+        channels
+    }
+
+    pub fn role_of(&self, user_id: UserId) -> Option<Role> {
+        if self.owner.id == user_id {
+            return Some(Role::Owner);
+        }
+        self.roles.get(&user_id.0).copied()
     }
 
-    pub fn get_participants(&self) -> &Vec<User> {
-        &self.participants
+    /// Grants `role` to the user or revokes their role with `None`
+    pub fn set_role(&mut self, user_id: UserId, role: Option<Role>) {
+        match role {
+            Some(role) => self.roles.insert(user_id.0, role),
+            None => self.roles.remove(&user_id.0),
+        };
     }
 
     pub fn get_owner(&self) -> User {
@@ -55,44 +431,442 @@ impl Giveaway {
         &self.text
     }
 
-    pub fn get_photo(&self) -> InputFile {
-        InputFile::file_id(&self.photo)
+    pub fn media(&self) -> Vec<Media> {
+        if self.media.is_empty() && !self.legacy_photo.is_empty() {
+            return vec![Media {
+                kind: MediaKind::Photo,
+                file_id: self.legacy_photo.clone(),
+            }];
+        }
+
+        self.media.clone()
     }
 
-    pub fn get_message(&self) -> &Option<Message> {
-        &self.message
+    /// The post is a text message: without media, or the follow-up of an album
+    /// since albums can't have inline keyboards
+    pub fn has_text_post(&self) -> bool {
+        self.media().len() != 1
     }
 
-    pub fn set_message(&mut self, message: Message) {
-        self.message = Some(message);
+    pub fn get_message(&self) -> Option<MessageRef> {
+        self.message_ref
+            .or_else(|| self.legacy_message.as_ref().map(MessageRef::from))
     }
 
-    pub fn check_user(&self, user: User) -> bool {
-        let user_ids: Vec<UserId> = self.participants.iter().map(|x| x.id).collect();
-        user_ids.contains(&user.id)
+    pub fn set_message(&mut self, message: &Message) {
+        self.message_ref = Some(MessageRef::from(message));
+        self.legacy_message = None;
     }
-}
 
-impl ToRedisArgs for GiveawaysList {
-    fn write_redis_args<W>(&self, out: &mut W)
-    where
-        W: ?Sized + redis::RedisWrite,
-    {
-        let json = serde_json::to_string(self).expect("Failed to serialize Giveaway");
-        out.write_arg(json.as_bytes());
+    /// Upgrades a record read from an older schema, returns `false` if it is already current
+    ///
+    /// Inline participants have to be moved with `take_legacy_participants` beforehand.
+    pub fn migrate(&mut self) -> bool {
+        if self.schema_version >= GIVEAWAY_SCHEMA_VERSION {
+            return false;
+        }
+
+        if self.message_ref.is_none() {
+            self.message_ref = self.legacy_message.as_ref().map(MessageRef::from);
+        }
+        self.legacy_message = None;
+        self.media = self.media();
+        self.legacy_photo.clear();
+        self.status = Some(self.status());
+        self.schema_version = GIVEAWAY_SCHEMA_VERSION;
+
+        true
+    }
+
+    /// Schedules the publication or moves an already scheduled one
+    pub fn schedule_publication(&mut self, channel: String, at: i64) -> AppResult<()> {
+        if self.status() != GiveawayStatus::Scheduled {
+            self.set_status(GiveawayStatus::Scheduled)?;
+        }
+        self.publication = Some(Publication { channel, at });
+        Ok(())
+    }
+
+    /// Drops the pending publication, a scheduled giveaway goes back to a draft
+    pub fn cancel_publication(&mut self) -> Option<Publication> {
+        if self.status() == GiveawayStatus::Scheduled {
+            self.status = Some(GiveawayStatus::Draft);
+        }
+        self.publication.take()
+    }
+
+    pub fn set_end_time(&mut self, ends_at: i64, winners_count: usize) -> AppResult<()> {
+        self.check_editable()?;
+        self.ends_at = Some(ends_at);
+        self.winners_count = winners_count;
+        self.check_caption_len()
+    }
+
+    pub fn get_winners(&self) -> &Vec<User> {
+        &self.winners
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.ended_at.is_some()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled_at.is_some()
+    }
+
+    /// Marks the giveaway cancelled, the record is kept for the list and verification
+    pub fn cancel(&mut self) -> AppResult<()> {
+        self.set_status(GiveawayStatus::Cancelled)?;
+        self.cancelled_at = Some(chrono::Utc::now().timestamp());
+        self.publication = None;
+        Ok(())
+    }
+
+    pub fn status(&self) -> GiveawayStatus {
+        if let Some(status) = self.status {
+            return status;
+        }
+
+        if self.is_cancelled() {
+            GiveawayStatus::Cancelled
+        } else if self.is_ended() {
+            GiveawayStatus::Ended
+        } else if !self.group_id.is_empty() {
+            GiveawayStatus::Active
+        } else if self.publication.is_some() {
+            GiveawayStatus::Scheduled
+        } else {
+            GiveawayStatus::Draft
+        }
+    }
+
+    /// Fails with a message for the user if the giveaway can't move to `next`
+    pub fn check_transition(&self, next: GiveawayStatus) -> AppResult<()> {
+        let current = self.status();
+
+        if current == next {
+            return Err(AppErrors::TextError(
+                Text::new("status.already").arg_text("status", current.text()),
+            ));
+        }
+
+        if !current.can_become(next) {
+            return Err(AppErrors::TextError(
+                Text::new("status.forbidden")
+                    .arg_text("status", current.text())
+                    .arg_text("next", next.text()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Fails with a message for the user once the giveaway is over
+    pub fn check_editable(&self) -> AppResult<()> {
+        if self.status().is_editable() {
+            return Ok(());
+        }
+
+        Err(AppErrors::TextError(
+            Text::new("status.not_editable").arg_text("status", self.status().text()),
+        ))
+    }
+
+    pub fn set_status(&mut self, next: GiveawayStatus) -> AppResult<()> {
+        self.check_transition(next)?;
+        self.status = Some(next);
+        Ok(())
+    }
+
+    /// Generates the secret seed if needed and returns its published hash
+    pub fn commit_seed(&mut self) -> AppResult<String> {
+        let seed = self.seed.get_or_insert_with(draw::generate_seed);
+        let commitment = draw::commitment(seed)?;
+        self.commitment = Some(commitment.clone());
+        Ok(commitment)
+    }
+
+    /// Seeded ordering of the participant snapshot
+    pub fn shuffle_participants(&mut self, snapshot: &[u64]) -> AppResult<Vec<u64>> {
+        let seed = self.seed.get_or_insert_with(draw::generate_seed);
+        draw::shuffle(seed, snapshot)
+    }
+
+    /// Stores the winners picked from the seeded ordering and the participants skipped on the way
+    pub fn record_draw(
+        &mut self,
+        snapshot: Vec<u64>,
+        winners: Vec<User>,
+        skipped: Vec<u64>,
+    ) -> AppResult<()> {
+        self.set_status(GiveawayStatus::Ended)?;
+        self.draw = Some(Draw {
+            snapshot,
+            winners: winners.iter().map(|user| user.id.0).collect(),
+            skipped,
+        });
+        self.winners = winners;
+        self.ended_at = Some(chrono::Utc::now().timestamp());
+        self.announcement_pending = true;
+        Ok(())
+    }
+
+    /// Participants in the seeded ordering who can still replace a winner
+    pub fn reroll_candidates(&self) -> AppResult<Vec<u64>> {
+        let (seed, draw) = match (&self.seed, &self.draw) {
+            (Some(seed), Some(draw)) => (seed, draw),
+            _ => return Ok(vec![]),
+        };
+
+        let excluded: Vec<u64> = self
+            .already_won()
+            .into_iter()
+            .chain(draw.skipped.iter().copied())
+            .collect();
+
+        Ok(draw::shuffle(seed, &draw.snapshot)?
+            .into_iter()
+            .filter(|id| !excluded.contains(id))
+            .collect())
+    }
+
+    /// Replaces the winner at `index` with `replacement`, recording the candidates skipped before it
+    pub fn replace_winner(
+        &mut self,
+        index: usize,
+        replacement: User,
+        skipped: Vec<u64>,
+    ) -> Option<&Reroll> {
+        let replaced = self.winners.get(index)?.clone();
+
+        if let Some(draw) = self.draw.as_mut() {
+            draw.skipped.extend(skipped);
+        }
+
+        self.winners[index] = replacement.clone();
+        self.rerolls.push(Reroll {
+            replaced,
+            replacement,
+            at: chrono::Utc::now().timestamp(),
+        });
+
+        self.rerolls.last()
+    }
+
+    /// Re-runs the draw and every reroll from the stored seed and snapshot
+    ///
+    /// Skipped participants are removed from the ordering first, the initial winners are the
+    /// head of what is left and every reroll takes the next participant who has not won yet.
+    pub fn verify(&self) -> AppResult<Option<Verification>> {
+        let (seed, draw) = match (&self.seed, &self.draw) {
+            (Some(seed), Some(draw)) => (seed, draw),
+            _ => return Ok(None),
+        };
+
+        let commitment_matches = match &self.commitment {
+            Some(commitment) => Some(draw::commitment(seed)? == *commitment),
+            None => None,
+        };
+
+        let ordering: Vec<u64> = draw::shuffle(seed, &draw.snapshot)?
+            .into_iter()
+            .filter(|id| !draw.skipped.contains(id))
+            .collect();
+
+        let mut winners: Vec<u64> = ordering.iter().take(draw.winners.len()).copied().collect();
+        let mut winners_match = winners == draw.winners;
+        let mut replaced: Vec<u64> = vec![];
+
+        for reroll in self.rerolls.iter() {
+            let excluded: Vec<u64> = winners.iter().chain(replaced.iter()).copied().collect();
+            let expected = draw::next_candidate(&ordering, &excluded);
+
+            winners_match &= expected == Some(reroll.replacement.id.0);
+
+            match winners.iter().position(|id| *id == reroll.replaced.id.0) {
+                Some(position) => winners[position] = reroll.replacement.id.0,
+                None => winners_match = false,
+            }
+            replaced.push(reroll.replaced.id.0);
+        }
+
+        let current: Vec<u64> = self.winners.iter().map(|user| user.id.0).collect();
+        winners_match &= winners == current;
+
+        Ok(Some(Verification {
+            commitment_matches,
+            winners_match,
+        }))
+    }
+
+    fn already_won(&self) -> Vec<u64> {
+        self.winners
+            .iter()
+            .chain(self.rerolls.iter().map(|reroll| &reroll.replaced))
+            .map(|user| user.id.0)
+            .collect()
+    }
+
+    /// Caption of the channel post
+    pub fn caption(&self) -> String {
+        self.render_caption(self.commitment.as_deref(), self.is_cancelled())
+    }
+
+    /// Fails with a message for the user if the post could outgrow Telegram's limit,
+    /// counting the seed hash and the cancellation notice that are added later
+    pub fn check_caption_len(&self) -> AppResult<()> {
+        let limit = if self.has_text_post() {
+            TEXT_LIMIT
+        } else {
+            CAPTION_LIMIT
+        };
+        let longest = self.render_caption(Some(&"0".repeat(64)), true);
+        // Telegram counts UTF-16 code units
+        let length = longest.encode_utf16().count();
+
+        if length <= limit {
+            return Ok(());
+        }
+
+        Err(AppErrors::TextError(
+            Text::new("post.too_long")
+                .arg("length", length)
+                .arg("limit", limit),
+        ))
+    }
+
+    fn render_caption(&self, commitment: Option<&str>, cancelled: bool) -> String {
+        let mut caption = self.text.clone();
+
+        if !self.prizes.is_empty() {
+            caption.push_str(&format!("\n\n{}", self.prizes_text(self.lang)));
+        }
+
+        if !self.rules.is_empty() {
+            let rules = self
+                .rules
+                .iter()
+                .map(|rule| format!("• {}", rule.description().render(self.lang)))
+                .collect::<Vec<String>>()
+                .join("\n");
+            caption.push_str(&format!("\n\n{}\n{rules}", tr!(self.lang, "post.rules")));
+        }
+
+        if let Some(ends_at) = self.ends_at {
+            let ends_at = format_timestamp(ends_at);
+            caption.push_str(&format!(
+                "\n\n{}",
+                tr!(self.lang, "post.ends_at", date = ends_at)
+            ));
+        }
+
+        if let Some(commitment) = commitment {
+            let commitment = tr!(self.lang, "post.commitment", hash = commitment);
+            caption.push_str(&format!("\n\n{commitment}"));
+        }
+
+        if cancelled {
+            caption.push_str(&format!("\n\n{}", tr!(self.lang, "post.cancelled")));
+        }
+
+        caption
+    }
+
+    pub fn get_rerolls(&self) -> &Vec<Reroll> {
+        &self.rerolls
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.finalized
+    }
+
+    pub fn finalize(&mut self) {
+        self.finalized = true;
+    }
+
+    /// Participants read from a record older than `Participants`, emptied on read
+    pub fn take_legacy_participants(&mut self) -> Vec<User> {
+        std::mem::take(&mut self.legacy_participants)
     }
 }
 
-impl FromRedisValue for GiveawaysList {
-    fn from_redis_value(v: &redis::Value) -> redis::RedisResult<Self> {
-        let json = String::from_redis_value(v)?;
-        serde_json::from_str(&json).map_err(|e| {
-            redis::RedisError::from((
-                redis::ErrorKind::TypeError,
-                "Failed to deserialize Giveaway",
-                format!("{e:?}"),
-            ))
-        })
+/// Participants of one giveaway: a set of user ids for atomic joins and counting
+/// and a hash with a profile snapshot of every participant
+pub struct Participants<'a> {
+    pub id: Uuid,
+    pub con: &'a mut DbConnection,
+}
+
+impl<'a> Participants<'a> {
+    pub fn new(id: Uuid, con: &'a mut DbConnection) -> Self {
+        Participants { id, con }
+    }
+
+    fn ids(&mut self) -> ParticipantIds<'_> {
+        RSet::new(format!("{PARTICIPANTS_KEY}{}", self.id), self.con)
+    }
+
+    fn profiles(&mut self) -> ParticipantProfiles<'_> {
+        RHashMap::new(format!("{PARTICIPANT_PROFILES_KEY}{}", self.id), self.con)
+    }
+
+    /// Adds the user, returns the count right after the join or `None` if they already take part.
+    /// The profile and the id are written and counted in one transaction
+    pub async fn add(&mut self, user: &User) -> AppResult<Option<usize>> {
+        let user_id = serde_json::to_string(&user.id.0)?;
+        let profile = serde_json::to_string(user)?;
+        let ids = self.ids().key;
+
+        let (_, added, count): (i64, i64, usize) = redis::pipe()
+            .atomic()
+            .hset(self.profiles().key, &user_id, profile)
+            .sadd(&ids, &user_id)
+            .scard(&ids)
+            .query_async(self.con)
+            .await?;
+        Ok((added > 0).then_some(count))
+    }
+
+    /// Removes the id and the profile in one transaction
+    pub async fn remove(&mut self, user_id: UserId) -> AppResult<()> {
+        let user_id = serde_json::to_string(&user_id.0)?;
+
+        redis::pipe()
+            .atomic()
+            .srem(self.ids().key, &user_id)
+            .hdel(self.profiles().key, &user_id)
+            .query_async::<()>(self.con)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn contains(&mut self, user_id: UserId) -> AppResult<bool> {
+        self.ids().contains(user_id.0).await
+    }
+
+    pub async fn count(&mut self) -> AppResult<usize> {
+        self.ids().len().await
+    }
+
+    pub async fn profile(&mut self, user_id: u64) -> AppResult<Option<User>> {
+        self.profiles().get(user_id).await
+    }
+
+    /// Profiles ordered by user id, the ids in this order are the snapshot a draw is made from
+    pub async fn all(&mut self) -> AppResult<Vec<User>> {
+        let ids = self.ids().members().await?;
+
+        let mut profiles = vec![];
+        let mut hash = self.profiles();
+        let mut scan = hash.scan(PROFILES_PAGE_SIZE);
+        while let Some(profile) = scan.next().await? {
+            profiles.push(profile);
+        }
+
+        profiles.retain(|(id, _)| ids.contains(id));
+        profiles.sort_by_key(|(id, _)| *id);
+
+        Ok(profiles.into_iter().map(|(_, user)| user).collect())
     }
 }
 
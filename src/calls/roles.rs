@@ -0,0 +1,133 @@
+use crate::calls::models::{Giveaway, SharedGiveaways};
+use crate::consts::SHARED_GIVEAWAYS_KEY;
+use crate::db::Db;
+use crate::errors::{AppErrors, AppResult};
+use crate::i18n::Text;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use teloxide::types::UserId;
+use uuid::Uuid;
+
+/// Role of a Telegram user in a giveaway, every role includes the rights of the ones below it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Role {
+    /// Sees the giveaway in the list and its participants
+    Viewer,
+    /// Publishes, configures, ends and rerolls the giveaway
+    Editor,
+    /// Cancels the giveaway and grants roles to others
+    Owner,
+}
+
+impl Role {
+    pub fn text(self) -> Text {
+        match self {
+            Role::Viewer => Text::new("role.viewer"),
+            Role::Editor => Text::new("role.editor"),
+            Role::Owner => Text::new("role.owner"),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = AppErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "owner" => Ok(Role::Owner),
+            _ => Err(AppErrors::TextError(
+                Text::new("role.unknown").arg("role", s),
+            )),
+        }
+    }
+}
+
+/// Result of looking a giveaway up on behalf of a user
+pub enum Access {
+    Granted {
+        /// Creator of the giveaway, their list is where it is stored
+        owner_id: u64,
+        role: Role,
+        giveaway: Box<Giveaway>,
+    },
+    Denied {
+        required: Role,
+    },
+    NotFound,
+}
+
+impl Access {
+    pub fn text(&self) -> Text {
+        match self {
+            Access::Granted { role, .. } => {
+                Text::new("access.granted").arg_text("role", role.text())
+            }
+            Access::Denied { required } => {
+                Text::new("access.denied").arg_text("role", required.text())
+            }
+            Access::NotFound => Text::new("access.not_found"),
+        }
+    }
+}
+
+/// Loads the giveaway if `user_id` has at least the `required` role in it
+pub async fn giveaway_access(
+    pool: Db,
+    user_id: UserId,
+    id: Uuid,
+    required: Role,
+) -> AppResult<Access> {
+    // Giveaways created before the global index are only found in the user's own list
+    let owner_id = pool.giveaways().owner(id).await?.unwrap_or(user_id.0);
+
+    let giveaway = match pool.giveaways().get(owner_id, id).await? {
+        Some(giveaway) => giveaway,
+        None => return Ok(Access::NotFound),
+    };
+
+    match giveaway.role_of(user_id) {
+        Some(role) if role >= required => Ok(Access::Granted {
+            owner_id,
+            role,
+            giveaway: Box::new(giveaway),
+        }),
+        Some(_) => Ok(Access::Denied { required }),
+        None => Ok(Access::NotFound),
+    }
+}
+
+/// Giveaways created by the user followed by the ones shared with them
+pub async fn accessible_giveaways(
+    pool: Db,
+    user_id: UserId,
+) -> AppResult<Vec<(Uuid, Role, Giveaway)>> {
+    let mut giveaways = pool
+        .giveaways()
+        .list(user_id.0)
+        .await?
+        .into_iter()
+        .map(|(id, giveaway)| (id, Role::Owner, giveaway))
+        .collect::<Vec<_>>();
+
+    let mut conn = pool.get().await?;
+    let shared_key = format!("{SHARED_GIVEAWAYS_KEY}{}", user_id.0);
+    let shared = SharedGiveaways::new(shared_key.clone(), &mut conn)
+        .members()
+        .await?;
+
+    for id in shared {
+        match giveaway_access(pool.clone(), user_id, id, Role::Viewer).await? {
+            Access::Granted { role, giveaway, .. } => giveaways.push((id, role, *giveaway)),
+            _ => {
+                // The giveaway was cancelled or the role revoked
+                SharedGiveaways::new(shared_key.clone(), &mut conn)
+                    .remove(id)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(giveaways)
+}
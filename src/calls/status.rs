@@ -0,0 +1,129 @@
+use crate::i18n::Text;
+use serde::{Deserialize, Serialize};
+
+/// Lifecycle status of a giveaway, see `can_become` for the allowed transitions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GiveawayStatus {
+    /// Created, not published or scheduled yet
+    Draft,
+    /// Waiting for the scheduler to publish it
+    Scheduled,
+    /// Published and open for joining
+    Active,
+    /// Published, joining is temporarily closed
+    Paused,
+    /// Winners are drawn
+    Ended,
+    Cancelled,
+    /// Ended or cancelled and hidden from the list by default
+    Archived,
+}
+
+impl GiveawayStatus {
+    /// Statuses in lifecycle order, the list groups giveaways in this order
+    pub const ALL: [GiveawayStatus; 7] = [
+        GiveawayStatus::Draft,
+        GiveawayStatus::Scheduled,
+        GiveawayStatus::Active,
+        GiveawayStatus::Paused,
+        GiveawayStatus::Ended,
+        GiveawayStatus::Cancelled,
+        GiveawayStatus::Archived,
+    ];
+
+    /// Shown in the list unless the user filters for the archive
+    pub const LISTED: [GiveawayStatus; 6] = [
+        GiveawayStatus::Draft,
+        GiveawayStatus::Scheduled,
+        GiveawayStatus::Active,
+        GiveawayStatus::Paused,
+        GiveawayStatus::Ended,
+        GiveawayStatus::Cancelled,
+    ];
+
+    /// Settings such as prizes and rules can still be changed
+    pub const EDITABLE: [GiveawayStatus; 4] = [
+        GiveawayStatus::Draft,
+        GiveawayStatus::Scheduled,
+        GiveawayStatus::Active,
+        GiveawayStatus::Paused,
+    ];
+
+    pub fn can_become(self, next: GiveawayStatus) -> bool {
+        use GiveawayStatus::*;
+
+        matches!(
+            (self, next),
+            (Draft, Scheduled | Active | Cancelled)
+                | (Scheduled, Draft | Active | Cancelled)
+                | (Active, Paused | Ended | Cancelled)
+                | (Paused, Active | Ended | Cancelled)
+                | (Ended | Cancelled, Archived)
+        )
+    }
+
+    /// Not published yet, so it can be published or scheduled
+    pub fn is_unpublished(self) -> bool {
+        matches!(self, GiveawayStatus::Draft | GiveawayStatus::Scheduled)
+    }
+
+    pub fn is_editable(self) -> bool {
+        GiveawayStatus::EDITABLE.contains(&self)
+    }
+
+    /// Alert for a user pressing the join button, `None` while joining is open
+    pub fn join_rejection(self) -> Option<Text> {
+        match self {
+            GiveawayStatus::Active => None,
+            GiveawayStatus::Draft | GiveawayStatus::Scheduled => {
+                Some(Text::new("join.not_started"))
+            }
+            GiveawayStatus::Paused => Some(Text::new("join.paused")),
+            GiveawayStatus::Ended | GiveawayStatus::Archived => Some(Text::new("join.ended")),
+            GiveawayStatus::Cancelled => Some(Text::new("join.cancelled")),
+        }
+    }
+
+    /// Heading of the group of giveaways with this status in the list
+    pub fn group_title(self) -> Text {
+        match self {
+            GiveawayStatus::Draft => Text::new("status.group.draft"),
+            GiveawayStatus::Scheduled => Text::new("status.group.scheduled"),
+            GiveawayStatus::Active => Text::new("status.group.active"),
+            GiveawayStatus::Paused => Text::new("status.group.paused"),
+            GiveawayStatus::Ended => Text::new("status.group.ended"),
+            GiveawayStatus::Cancelled => Text::new("status.group.cancelled"),
+            GiveawayStatus::Archived => Text::new("status.group.archived"),
+        }
+    }
+
+    pub fn text(self) -> Text {
+        match self {
+            GiveawayStatus::Draft => Text::new("status.draft"),
+            GiveawayStatus::Scheduled => Text::new("status.scheduled"),
+            GiveawayStatus::Active => Text::new("status.active"),
+            GiveawayStatus::Paused => Text::new("status.paused"),
+            GiveawayStatus::Ended => Text::new("status.ended"),
+            GiveawayStatus::Cancelled => Text::new("status.cancelled"),
+            GiveawayStatus::Archived => Text::new("status.archived"),
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            GiveawayStatus::Draft => "d",
+            GiveawayStatus::Scheduled => "s",
+            GiveawayStatus::Active => "a",
+            GiveawayStatus::Paused => "p",
+            GiveawayStatus::Ended => "e",
+            GiveawayStatus::Cancelled => "c",
+            GiveawayStatus::Archived => "r",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        GiveawayStatus::ALL
+            .into_iter()
+            .find(|status| status.code() == code)
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::time::Duration;
 
@@ -16,7 +17,9 @@ use crate::errors::AppResult;
 /// - HLEN: returns the number of fields contained in the hash stored at key.
 /// - HEXISTS: returns if field is an existing field in the hash stored at key.
 /// - HEXPIRE: sets an expiration on the field.
-/// - HDEL: removes the specified fields from the hash stored at key.
+/// - HPERSIST: removes the expiration of the field.
+/// - HSCAN: iterates the fields of the hash a page at a time.
+/// - WATCH/MULTI: sets a field only if nobody changed the hash meanwhile.
 pub struct RHashMap<'a, C, K, F, V> {
     pub key: K,
     pub con: &'a mut C,
@@ -78,6 +81,164 @@ where
         }
     }
 
+    /// Get the values of several fields, `None` for the missing ones
+    ///
+    /// ### Redis Command
+    /// HMGET
+    pub async fn get_many(&mut self, fields: &[F]) -> AppResult<Vec<Option<V>>> {
+        if fields.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let fields = fields
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        let values: Vec<Option<String>> = redis::cmd("HMGET")
+            .arg(&self.key)
+            .arg(fields)
+            .query_async(self.con)
+            .await?;
+
+        let mut result = Vec::new();
+        for value in values {
+            result.push(
+                value
+                    .map(|value| serde_json::from_str(&value))
+                    .transpose()?,
+            );
+        }
+        Ok(result)
+    }
+
+    /// Number of fields
+    ///
+    /// ### Redis Command
+    /// HLEN
+    #[allow(dead_code)]
+    pub async fn len(&mut self) -> AppResult<usize> {
+        self.con.hlen(&self.key).await.map_err(Into::into)
+    }
+
+    /// Check if a field is present
+    ///
+    /// ### Redis Command
+    /// HEXISTS
+    pub async fn contains(&mut self, field: F) -> AppResult<bool> {
+        let field = serde_json::to_string(&field)?;
+        self.con.hexists(&self.key, field).await.map_err(Into::into)
+    }
+
+    /// Add `delta` to an integer value, a missing field counts as 0. Returns the new value
+    ///
+    /// ### Redis Command
+    /// HINCRBY
+    #[allow(dead_code)]
+    pub async fn incr(&mut self, field: F, delta: i64) -> AppResult<i64> {
+        let field = serde_json::to_string(&field)?;
+        self.con
+            .hincr(&self.key, field, delta)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Remove the field after `ttl`, returns `false` if there is no such field
+    ///
+    /// ### Redis Command
+    /// HPEXPIRE
+    pub async fn expire(&mut self, field: F, ttl: Duration) -> AppResult<bool> {
+        let field = serde_json::to_string(&field)?;
+        let replies: Vec<i64> = self
+            .con
+            .hpexpire(&self.key, ttl.as_millis() as i64, ExpireOption::NONE, field)
+            .await?;
+        Ok(replies.first().is_some_and(|reply| *reply == 1))
+    }
+
+    /// Keep the field until it is removed, returns `false` if it had no expiration
+    ///
+    /// ### Redis Command
+    /// HPERSIST
+    #[allow(dead_code)]
+    pub async fn persist(&mut self, field: F) -> AppResult<bool> {
+        let field = serde_json::to_string(&field)?;
+        let replies: Vec<i64> = self.con.hpersist(&self.key, field).await?;
+        Ok(replies.first().is_some_and(|reply| *reply == 1))
+    }
+
+    /// Iterate the fields without loading the whole hash, about `count` at a time.
+    /// Fields changed during the scan may be missed or returned twice
+    ///
+    /// ### Redis Command
+    /// HSCAN
+    pub fn scan(&mut self, count: usize) -> RHashScan<'_, 'a, C, K, F, V> {
+        RHashScan {
+            map: self,
+            cursor: Some(0),
+            count,
+            page: VecDeque::new(),
+        }
+    }
+
+    /// Set the field to `new` if its value is still `expected`, `None` meaning no field.
+    /// Values are compared by their JSON. Returns `false` if the value was different
+    /// or the hash changed before the write
+    ///
+    /// ### Redis Commands
+    /// WATCH, HGET, MULTI, HSET, EXEC
+    pub async fn compare_and_set(
+        &mut self,
+        field: F,
+        expected: Option<V>,
+        new: V,
+    ) -> AppResult<bool> {
+        let field = serde_json::to_string(&field)?;
+        let expected = expected
+            .map(|value| serde_json::to_string(&value))
+            .transpose()?;
+        let new = serde_json::to_string(&new)?;
+
+        redis::cmd("WATCH")
+            .arg(&self.key)
+            .query_async::<()>(self.con)
+            .await?;
+
+        let current: Option<String> = match self.con.hget(&self.key, &field).await {
+            Ok(current) => current,
+            Err(e) => {
+                self.unwatch().await;
+                return Err(e.into());
+            }
+        };
+        if current != expected {
+            self.unwatch().await;
+            return Ok(false);
+        }
+
+        // EXEC replies nil if the watched hash was changed
+        let written: Option<(i64,)> = match redis::pipe()
+            .atomic()
+            .hset(&self.key, field, new)
+            .query_async(self.con)
+            .await
+        {
+            Ok(written) => written,
+            Err(e) => {
+                // EXEC may not have run, the pooled connection must not keep the watch
+                self.unwatch().await;
+                return Err(e.into());
+            }
+        };
+        Ok(written.is_some())
+    }
+
+    /// Drops the watch of `compare_and_set`, a failure only leaves it to the next EXEC
+    async fn unwatch(&mut self) {
+        if let Err(e) = redis::cmd("UNWATCH").query_async::<()>(self.con).await {
+            log::warn!("Cannot unwatch {:?}: {e}", self.key);
+        }
+    }
+
     /// Get multiple values by keys
     ///
     /// ### Redis Command
@@ -99,12 +260,206 @@ where
         }
     }
 
-    /// Remove a key
+    /// Remove the whole hash
     ///
     /// ### Redis Command
-    /// HDEL
-    pub async fn remove(&mut self, field: F) -> AppResult<()> {
-        let field = serde_json::to_string(&field)?;
-        self.con.hdel(&self.key, field).await.map_err(Into::into)
+    /// DEL
+    #[allow(dead_code)]
+    pub async fn clear(&mut self) -> AppResult<()> {
+        self.con.del(&self.key).await.map_err(Into::into)
+    }
+}
+
+/// Fields of an `RHashMap` fetched a page at a time, see `RHashMap::scan`
+pub struct RHashScan<'s, 'a, C, K, F, V> {
+    map: &'s mut RHashMap<'a, C, K, F, V>,
+    /// `None` once the last page was fetched
+    cursor: Option<u64>,
+    count: usize,
+    page: VecDeque<(F, V)>,
+}
+
+impl<C, K, F, V> RHashScan<'_, '_, C, K, F, V>
+where
+    C: AsyncCommands,
+    K: ToRedisArgs + Send + Sync + Debug,
+    F: DeserializeOwned + Serialize,
+    V: Serialize + DeserializeOwned,
+{
+    /// Next field-value pair, `None` when the hash is scanned through
+    pub async fn next(&mut self) -> AppResult<Option<(F, V)>> {
+        while self.page.is_empty() {
+            let cursor = match self.cursor {
+                Some(cursor) => cursor,
+                None => return Ok(None),
+            };
+
+            let (next, values): (u64, Vec<(String, String)>) = redis::cmd("HSCAN")
+                .arg(&self.map.key)
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(self.count)
+                .query_async(self.map.con)
+                .await?;
+
+            for (field, value) in values {
+                self.page
+                    .push_back((serde_json::from_str(&field)?, serde_json::from_str(&value)?));
+            }
+            self.cursor = (next != 0).then_some(next);
+        }
+
+        Ok(self.page.pop_front())
+    }
+}
+
+/// # Basic commands
+/// https://redis.io/docs/latest/develop/data-types/sorted-sets/
+/// - ZADD: adds a member with a score or updates the score of an existing member.
+/// - ZRANGEBYSCORE: returns the members with a score within the given range.
+/// - ZREM: removes the specified members from the sorted set stored at key.
+pub struct RSortedSet<'a, C, K, M> {
+    pub key: K,
+    pub con: &'a mut C,
+    pub _marker: std::marker::PhantomData<M>,
+}
+
+impl<'a, C, K, M> RSortedSet<'a, C, K, M>
+where
+    C: AsyncCommands,
+    K: ToRedisArgs + Send + Sync + Debug,
+    M: Serialize + DeserializeOwned,
+{
+    pub fn new(key: K, con: &'a mut C) -> Self {
+        RSortedSet {
+            key,
+            con,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Add a member or update its score
+    ///
+    /// ### Redis Command
+    /// ZADD
+    pub async fn insert(&mut self, member: M, score: i64) -> AppResult<()> {
+        let member = serde_json::to_string(&member)?;
+        self.con
+            .zadd::<_, _, _, ()>(&self.key, member, score)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Get all members with a score less than or equal to `max`
+    ///
+    /// ### Redis Command
+    /// ZRANGEBYSCORE
+    pub async fn range_to(&mut self, max: i64) -> AppResult<Vec<(M, i64)>> {
+        let values: Vec<(String, i64)> = self
+            .con
+            .zrangebyscore_withscores(&self.key, "-inf", max)
+            .await?;
+
+        let mut result = Vec::new();
+        for (member, score) in values {
+            let member: M = serde_json::from_str(&member)?;
+            result.push((member, score));
+        }
+        Ok(result)
+    }
+
+    /// Remove a member
+    ///
+    /// ### Redis Command
+    /// ZREM
+    pub async fn remove(&mut self, member: M) -> AppResult<()> {
+        let member = serde_json::to_string(&member)?;
+        self.con
+            .zrem::<_, _, ()>(&self.key, member)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+/// # Basic commands
+/// https://redis.io/docs/latest/develop/data-types/sets/
+/// - SADD: adds a new member to a set.
+/// - SISMEMBER: tests a member for set membership.
+/// - SCARD: returns the size (a.k.a. cardinality) of a set.
+/// - SMEMBERS: returns all members of the set.
+pub struct RSet<'a, C, K, M> {
+    pub key: K,
+    pub con: &'a mut C,
+    pub _marker: std::marker::PhantomData<M>,
+}
+
+impl<'a, C, K, M> RSet<'a, C, K, M>
+where
+    C: AsyncCommands,
+    K: ToRedisArgs + Send + Sync + Debug,
+    M: Serialize + DeserializeOwned,
+{
+    pub fn new(key: K, con: &'a mut C) -> Self {
+        RSet {
+            key,
+            con,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Add a member, returns `false` if it was already present
+    ///
+    /// ### Redis Command
+    /// SADD
+    pub async fn insert(&mut self, member: M) -> AppResult<bool> {
+        let member = serde_json::to_string(&member)?;
+        let added: i64 = self.con.sadd(&self.key, member).await?;
+        Ok(added > 0)
+    }
+
+    /// Check if a member is present
+    ///
+    /// ### Redis Command
+    /// SISMEMBER
+    pub async fn contains(&mut self, member: M) -> AppResult<bool> {
+        let member = serde_json::to_string(&member)?;
+        self.con
+            .sismember(&self.key, member)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Number of members
+    ///
+    /// ### Redis Command
+    /// SCARD
+    pub async fn len(&mut self) -> AppResult<usize> {
+        self.con.scard(&self.key).await.map_err(Into::into)
+    }
+
+    /// All members
+    ///
+    /// ### Redis Command
+    /// SMEMBERS
+    pub async fn members(&mut self) -> AppResult<Vec<M>> {
+        let values: Vec<String> = self.con.smembers(&self.key).await?;
+
+        let mut result = Vec::new();
+        for value in values {
+            result.push(serde_json::from_str(&value)?);
+        }
+        Ok(result)
+    }
+
+    /// Remove a member
+    ///
+    /// ### Redis Command
+    /// SREM
+    pub async fn remove(&mut self, member: M) -> AppResult<()> {
+        let member = serde_json::to_string(&member)?;
+        self.con
+            .srem::<_, _, ()>(&self.key, member)
+            .await
+            .map_err(Into::into)
     }
 }
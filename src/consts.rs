@@ -1,2 +1,28 @@
 pub static USER_GIVEAWAY_KEY: &str = "giveaway:";
+pub static GIVEAWAY_INDEX_KEY: &str = "giveaway_index";
+pub static SHARED_GIVEAWAYS_KEY: &str = "shared_giveaways:";
+pub static KNOWN_USERS_KEY: &str = "known_users";
+pub static VERIFIED_CHANNELS_KEY: &str = "verified_channels:";
+pub static USER_LANGUAGES_KEY: &str = "user_languages";
+pub static PENDING_ALBUMS_KEY: &str = "pending_albums";
+pub static RECENT_WINNERS_KEY: &str = "recent_winners:";
+pub static ALBUM_TTL_SECS: u64 = 60;
 pub static FILENAME: &str = "participants.txt";
+pub static PARTICIPANTS_KEY: &str = "participants:";
+pub static PARTICIPANT_PROFILES_KEY: &str = "participant_profiles:";
+pub static KEYBOARD_ROW_SIZE: usize = 2;
+pub static GIVEAWAYS_PAGE_SIZE: usize = 5;
+pub static PROFILES_PAGE_SIZE: usize = 500;
+pub static GIVEAWAYS_SCAN_SIZE: usize = 50;
+pub static DEFAULT_WINNERS_COUNT: usize = 1;
+pub static CAPTION_LIMIT: usize = 1024;
+pub static TEXT_LIMIT: usize = 4096;
+pub static END_SCHEDULE_KEY: &str = "schedule:end";
+pub static PUBLISH_SCHEDULE_KEY: &str = "schedule:publish";
+pub static ANNOUNCE_SCHEDULE_KEY: &str = "schedule:announce";
+pub static ANNOUNCE_RETRY_SECS: i64 = 300;
+pub static ANNOUNCE_GIVE_UP_SECS: i64 = 24 * 60 * 60;
+/// Pause between notices to participants, keeps bulk sends under Telegram's 30 messages a second
+pub static NOTICE_INTERVAL_MS: u64 = 50;
+pub static SCHEDULER_INTERVAL_SECS: u64 = 30;
+pub static DATE_FORMAT: &str = "%d.%m.%Y %H:%M";
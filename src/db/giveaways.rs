@@ -0,0 +1,227 @@
+use super::Connections;
+use crate::calls::models::{Giveaway, GiveawayIndex, GiveawaysStorage, Participants};
+use crate::consts::{GIVEAWAY_INDEX_KEY, GIVEAWAYS_SCAN_SIZE, USER_GIVEAWAY_KEY};
+use crate::errors::AppResult;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use teloxide::types::{User, UserId};
+use uuid::Uuid;
+
+/// Giveaways and their participants, whatever they are stored in
+///
+/// Giveaways are kept per owner, `owner` finds the owner of a giveaway by its id alone.
+#[async_trait]
+pub trait GiveawayRepository: Send + Sync {
+    /// Stores a new giveaway of `owner_id`
+    async fn create(&self, owner_id: u64, id: Uuid, giveaway: Giveaway) -> AppResult<()>;
+
+    async fn get(&self, owner_id: u64, id: Uuid) -> AppResult<Option<Giveaway>>;
+
+    async fn owner(&self, id: Uuid) -> AppResult<Option<u64>>;
+
+    /// Every giveaway of `owner_id`, in no particular order
+    async fn list(&self, owner_id: u64) -> AppResult<Vec<(Uuid, Giveaway)>>;
+
+    /// Replaces the stored giveaway, stores it if there is none yet
+    async fn update(&self, owner_id: u64, id: Uuid, giveaway: Giveaway) -> AppResult<()>;
+
+    /// Adds the user, returns the participant count right after the join,
+    /// or `None` if they already take part
+    async fn add_participant(&self, id: Uuid, user: &User) -> AppResult<Option<usize>>;
+
+    /// Removes the user with their profile
+    async fn remove_participant(&self, id: Uuid, user_id: UserId) -> AppResult<()>;
+
+    async fn is_participant(&self, id: Uuid, user_id: UserId) -> AppResult<bool>;
+
+    async fn participant_count(&self, id: Uuid) -> AppResult<usize>;
+
+    async fn participant(&self, id: Uuid, user_id: u64) -> AppResult<Option<User>>;
+
+    /// Profiles ordered by user id, the ids in this order are the snapshot a draw is made from
+    async fn participants(&self, id: Uuid) -> AppResult<Vec<User>>;
+}
+
+/// Giveaways in a hash per owner, the index hash and `Participants`
+pub struct RedisGiveaways {
+    connections: Connections,
+}
+
+impl RedisGiveaways {
+    pub(super) fn new(connections: Connections) -> Self {
+        RedisGiveaways { connections }
+    }
+}
+
+#[async_trait]
+impl GiveawayRepository for RedisGiveaways {
+    async fn create(&self, owner_id: u64, id: Uuid, giveaway: Giveaway) -> AppResult<()> {
+        self.update(owner_id, id, giveaway).await
+    }
+
+    async fn get(&self, owner_id: u64, id: Uuid) -> AppResult<Option<Giveaway>> {
+        let mut conn = self.connections.get().await?;
+        let key = format!("{USER_GIVEAWAY_KEY}{owner_id}");
+        GiveawaysStorage::new(key, &mut conn).get(id).await
+    }
+
+    async fn owner(&self, id: Uuid) -> AppResult<Option<u64>> {
+        let mut conn = self.connections.get().await?;
+        GiveawayIndex::new(GIVEAWAY_INDEX_KEY.to_string(), &mut conn)
+            .get(id)
+            .await
+    }
+
+    async fn list(&self, owner_id: u64) -> AppResult<Vec<(Uuid, Giveaway)>> {
+        let mut conn = self.connections.get().await?;
+        let key = format!("{USER_GIVEAWAY_KEY}{owner_id}");
+        let mut storage = GiveawaysStorage::new(key, &mut conn);
+
+        // A page at a time, owners with a long history have large hashes
+        let mut giveaways = vec![];
+        let mut scan = storage.scan(GIVEAWAYS_SCAN_SIZE);
+        while let Some(giveaway) = scan.next().await? {
+            giveaways.push(giveaway);
+        }
+        Ok(giveaways)
+    }
+
+    /// Writes the index entry as well, giveaways created before the index get it on update
+    async fn update(&self, owner_id: u64, id: Uuid, giveaway: Giveaway) -> AppResult<()> {
+        let mut conn = self.connections.get().await?;
+        let key = format!("{USER_GIVEAWAY_KEY}{owner_id}");
+        GiveawaysStorage::new(key, &mut conn)
+            .insert(id, giveaway, None)
+            .await?;
+        GiveawayIndex::new(GIVEAWAY_INDEX_KEY.to_string(), &mut conn)
+            .insert(id, owner_id, None)
+            .await
+    }
+
+    async fn add_participant(&self, id: Uuid, user: &User) -> AppResult<Option<usize>> {
+        let mut conn = self.connections.get().await?;
+        Participants::new(id, &mut conn).add(user).await
+    }
+
+    async fn remove_participant(&self, id: Uuid, user_id: UserId) -> AppResult<()> {
+        let mut conn = self.connections.get().await?;
+        Participants::new(id, &mut conn).remove(user_id).await
+    }
+
+    async fn is_participant(&self, id: Uuid, user_id: UserId) -> AppResult<bool> {
+        let mut conn = self.connections.get().await?;
+        Participants::new(id, &mut conn).contains(user_id).await
+    }
+
+    async fn participant_count(&self, id: Uuid) -> AppResult<usize> {
+        let mut conn = self.connections.get().await?;
+        Participants::new(id, &mut conn).count().await
+    }
+
+    async fn participant(&self, id: Uuid, user_id: u64) -> AppResult<Option<User>> {
+        let mut conn = self.connections.get().await?;
+        Participants::new(id, &mut conn).profile(user_id).await
+    }
+
+    async fn participants(&self, id: Uuid) -> AppResult<Vec<User>> {
+        let mut conn = self.connections.get().await?;
+        Participants::new(id, &mut conn).all().await
+    }
+}
+
+/// Giveaways in maps of the process, lost on restart
+#[derive(Default)]
+pub struct MemoryGiveaways {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Default)]
+struct MemoryData {
+    giveaways: HashMap<Uuid, (u64, Giveaway)>,
+    participants: HashMap<Uuid, BTreeMap<u64, User>>,
+}
+
+impl MemoryGiveaways {
+    fn data(&self) -> std::sync::MutexGuard<'_, MemoryData> {
+        self.data.lock().expect("Memory giveaways lock is poisoned")
+    }
+}
+
+#[async_trait]
+impl GiveawayRepository for MemoryGiveaways {
+    async fn create(&self, owner_id: u64, id: Uuid, giveaway: Giveaway) -> AppResult<()> {
+        self.update(owner_id, id, giveaway).await
+    }
+
+    async fn get(&self, owner_id: u64, id: Uuid) -> AppResult<Option<Giveaway>> {
+        Ok(self
+            .data()
+            .giveaways
+            .get(&id)
+            .filter(|(owner, _)| *owner == owner_id)
+            .map(|(_, giveaway)| giveaway.clone()))
+    }
+
+    async fn owner(&self, id: Uuid) -> AppResult<Option<u64>> {
+        Ok(self.data().giveaways.get(&id).map(|(owner, _)| *owner))
+    }
+
+    async fn list(&self, owner_id: u64) -> AppResult<Vec<(Uuid, Giveaway)>> {
+        Ok(self
+            .data()
+            .giveaways
+            .iter()
+            .filter(|(_, (owner, _))| *owner == owner_id)
+            .map(|(id, (_, giveaway))| (*id, giveaway.clone()))
+            .collect())
+    }
+
+    async fn update(&self, owner_id: u64, id: Uuid, giveaway: Giveaway) -> AppResult<()> {
+        self.data().giveaways.insert(id, (owner_id, giveaway));
+        Ok(())
+    }
+
+    async fn add_participant(&self, id: Uuid, user: &User) -> AppResult<Option<usize>> {
+        let mut data = self.data();
+        let participants = data.participants.entry(id).or_default();
+        let added = participants.insert(user.id.0, user.clone()).is_none();
+        Ok(added.then_some(participants.len()))
+    }
+
+    async fn remove_participant(&self, id: Uuid, user_id: UserId) -> AppResult<()> {
+        if let Some(participants) = self.data().participants.get_mut(&id) {
+            participants.remove(&user_id.0);
+        }
+        Ok(())
+    }
+
+    async fn is_participant(&self, id: Uuid, user_id: UserId) -> AppResult<bool> {
+        Ok(self
+            .data()
+            .participants
+            .get(&id)
+            .is_some_and(|participants| participants.contains_key(&user_id.0)))
+    }
+
+    async fn participant_count(&self, id: Uuid) -> AppResult<usize> {
+        Ok(self.data().participants.get(&id).map_or(0, BTreeMap::len))
+    }
+
+    async fn participant(&self, id: Uuid, user_id: u64) -> AppResult<Option<User>> {
+        Ok(self
+            .data()
+            .participants
+            .get(&id)
+            .and_then(|participants| participants.get(&user_id).cloned()))
+    }
+
+    async fn participants(&self, id: Uuid) -> AppResult<Vec<User>> {
+        Ok(self
+            .data()
+            .participants
+            .get(&id)
+            .map(|participants| participants.values().cloned().collect())
+            .unwrap_or_default())
+    }
+}
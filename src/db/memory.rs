@@ -0,0 +1,674 @@
+use super::sqlite::{KvChange, KvItem, SqliteKv};
+use crate::errors::AppResult;
+use redis::aio::ConnectionLike;
+use redis::{Arg, Cmd, ErrorKind, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Keys of the in-memory database, shared by all of its connections
+#[derive(Clone, Default)]
+pub struct MemoryDb {
+    data: Arc<Mutex<Data>>,
+    store: Option<Arc<Store>>,
+}
+
+#[derive(Default)]
+struct Data {
+    entries: HashMap<Vec<u8>, Entry>,
+    /// Number of the last write and of the last write to every key, for WATCH
+    writes: u64,
+    written_at: HashMap<Vec<u8>, u64>,
+}
+
+/// Where the changed keys are written after every command
+struct Store {
+    kv: SqliteKv,
+    /// Keeps the writes in the order of the commands
+    writes: tokio::sync::Mutex<()>,
+}
+
+#[derive(Clone)]
+enum Entry {
+    Hash(HashMap<Vec<u8>, Field>),
+    Set(HashSet<Vec<u8>>),
+    SortedSet(BTreeMap<Vec<u8>, f64>),
+}
+
+#[derive(Clone)]
+struct Field {
+    value: Vec<u8>,
+    /// Unix time in milliseconds
+    expires_at: Option<u64>,
+}
+
+impl Field {
+    fn is_live(&self) -> bool {
+        self.expires_at.is_none_or(|at| at > now_millis())
+    }
+}
+
+/// Keys a connection watches and the write it started watching at
+struct Watch {
+    keys: Vec<Vec<u8>>,
+    since: u64,
+}
+
+/// Replies of the commands, `None` if a watched key was changed and nothing ran
+type Replies = RedisResult<Option<Vec<Value>>>;
+
+/// Written keys as they were before the commands, `None` if a key didn't exist
+type Previous = HashMap<Vec<u8>, Option<Entry>>;
+
+impl MemoryDb {
+    /// Database that loads its keys from SQLite and writes every change back
+    pub async fn persistent(kv: SqliteKv) -> AppResult<Self> {
+        let mut data = Data::default();
+        for (key, item, value) in kv.load().await? {
+            let entry = data.entries.entry(key);
+            match value {
+                KvItem::Field { value, expires_at } => {
+                    if let Entry::Hash(hash) = entry.or_insert_with(|| Entry::Hash(HashMap::new()))
+                    {
+                        hash.insert(item, Field { value, expires_at });
+                    }
+                }
+                KvItem::SetMember => {
+                    if let Entry::Set(set) = entry.or_insert_with(|| Entry::Set(HashSet::new())) {
+                        set.insert(item);
+                    }
+                }
+                KvItem::SortedSetMember { score } => {
+                    if let Entry::SortedSet(set) =
+                        entry.or_insert_with(|| Entry::SortedSet(BTreeMap::new()))
+                    {
+                        set.insert(item, score);
+                    }
+                }
+            }
+        }
+
+        Ok(MemoryDb {
+            data: Arc::new(Mutex::new(data)),
+            store: Some(Arc::new(Store {
+                kv,
+                writes: tokio::sync::Mutex::new(()),
+            })),
+        })
+    }
+
+    pub fn connection(&self) -> MemoryConnection {
+        MemoryConnection {
+            db: self.clone(),
+            watch: None,
+        }
+    }
+
+    fn data(&self) -> std::sync::MutexGuard<'_, Data> {
+        self.data.lock().expect("Memory db lock is poisoned")
+    }
+
+    async fn run(&self, commands: &[&Cmd], watch: Option<Watch>) -> Replies {
+        let Some(store) = &self.store else {
+            return self.exec(commands, watch, false).0;
+        };
+
+        let _write = store.writes.lock().await;
+        let (result, changes, previous) = self.exec(commands, watch, true);
+
+        // Memory is changed first, so it is put back if the change can't be saved
+        if !changes.is_empty()
+            && let Err(e) = store.kv.save(changes).await
+        {
+            self.restore(previous);
+            return Err(RedisError::from((
+                ErrorKind::IoError,
+                "Cannot save keys",
+                e.to_string(),
+            )));
+        }
+
+        result
+    }
+
+    fn restore(&self, previous: Previous) {
+        let mut data = self.data();
+        for (key, entry) in previous {
+            match entry {
+                Some(entry) => data.entries.insert(key, entry),
+                None => data.entries.remove(&key),
+            };
+        }
+    }
+
+    /// Runs the commands under one lock, so a pipeline is atomic.
+    /// With `track` also returns what to write back and the written keys before the commands
+    fn exec(
+        &self,
+        commands: &[&Cmd],
+        watch: Option<Watch>,
+        track: bool,
+    ) -> (Replies, Vec<KvChange>, Previous) {
+        let mut data = self.data();
+
+        if let Some(watch) = watch {
+            let changed = watch
+                .keys
+                .iter()
+                .any(|key| data.written_at.get(key).is_some_and(|at| *at > watch.since));
+            if changed {
+                return (Ok(None), vec![], Previous::new());
+            }
+        }
+
+        let mut written = vec![];
+        let mut previous = Previous::new();
+        let result = commands
+            .iter()
+            .map(|cmd| {
+                let args = args(cmd);
+                let items = written_items(&args);
+                if track {
+                    for (key, _) in &items {
+                        if !previous.contains_key(key) {
+                            previous.insert(key.clone(), data.entries.get(key).cloned());
+                        }
+                    }
+                }
+                written.extend(items);
+                exec(&mut data.entries, &args)
+            })
+            .collect::<RedisResult<Vec<_>>>()
+            .map(Some);
+
+        let mut keys = written
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+
+        // Expired fields are only hidden by reads, they are dropped when the hash is written
+        for key in &keys {
+            if let Some(Entry::Hash(hash)) = data.entries.get_mut(key) {
+                hash.retain(|name, field| {
+                    let live = field.is_live();
+                    if !live {
+                        written.push((key.clone(), Some(name.clone())));
+                    }
+                    live
+                });
+            }
+        }
+
+        if !keys.is_empty() {
+            data.writes += 1;
+            let at = data.writes;
+            for key in keys {
+                data.written_at.insert(key, at);
+            }
+        }
+
+        if !track {
+            return (result, vec![], previous);
+        }
+
+        // A removed key sorts before its elements, which are then stored as they are now
+        written.sort();
+        written.dedup();
+        let changes = written
+            .into_iter()
+            .map(|(key, item)| {
+                let Some(item) = item else {
+                    return KvChange::Remove { key, item: None };
+                };
+                let value = match data.entries.get(&key) {
+                    Some(Entry::Hash(hash)) => hash.get(&item).map(|field| KvItem::Field {
+                        value: field.value.clone(),
+                        expires_at: field.expires_at,
+                    }),
+                    Some(Entry::Set(set)) => set.contains(&item).then_some(KvItem::SetMember),
+                    Some(Entry::SortedSet(set)) => set
+                        .get(&item)
+                        .map(|score| KvItem::SortedSetMember { score: *score }),
+                    None => None,
+                };
+                match value {
+                    Some(value) => KvChange::Put { key, item, value },
+                    None => KvChange::Remove {
+                        key,
+                        item: Some(item),
+                    },
+                }
+            })
+            .collect();
+
+        (result, changes, previous)
+    }
+}
+
+pub struct MemoryConnection {
+    db: MemoryDb,
+    watch: Option<Watch>,
+}
+
+impl ConnectionLike for MemoryConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let args = args(cmd);
+
+            // Watches belong to the connection, the commands run on the shared keys
+            match String::from_utf8_lossy(&args[0]).to_uppercase().as_str() {
+                "WATCH" => {
+                    let since = self.db.data().writes;
+                    let watch = self.watch.get_or_insert(Watch {
+                        keys: vec![],
+                        since,
+                    });
+                    watch.keys.extend_from_slice(&args[1..]);
+                    Ok(Value::Okay)
+                }
+                "UNWATCH" => {
+                    self.watch = None;
+                    Ok(Value::Okay)
+                }
+                _ => {
+                    let values = self.db.run(&[cmd], None).await?;
+                    Ok(values
+                        .and_then(|mut values| values.pop())
+                        .unwrap_or(Value::Nil))
+                }
+            }
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        _count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let commands = cmd.cmd_iter().collect::<Vec<_>>();
+            // A transaction skips the replies to MULTI and the queued commands,
+            // its results come in one reply to EXEC, nil if a watched key changed
+            if offset > 0 {
+                let values = self.db.run(&commands, self.watch.take()).await?;
+                Ok(vec![values.map(Value::Array).unwrap_or(Value::Nil)])
+            } else {
+                Ok(self.db.run(&commands, None).await?.unwrap_or_default())
+            }
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+}
+
+fn args(cmd: &Cmd) -> Vec<Vec<u8>> {
+    cmd.args_iter()
+        .map(|arg| match arg {
+            Arg::Simple(arg) => arg.to_vec(),
+            // Key scans return every key at once, so the cursor is always the first one
+            Arg::Cursor => b"0".to_vec(),
+        })
+        .collect()
+}
+
+fn exec(data: &mut HashMap<Vec<u8>, Entry>, args: &[Vec<u8>]) -> RedisResult<Value> {
+    let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+    let key = args.get(1).cloned().unwrap_or_default();
+    let rest = args.get(2..).unwrap_or_default();
+
+    let reply = match name.as_str() {
+        "PING" => Value::SimpleString("PONG".to_string()),
+        "DEL" => count(args[1..].iter().filter(|key| data.remove(*key).is_some())),
+        "HSET" => match data.entry(key).or_insert(Entry::Hash(HashMap::new())) {
+            Entry::Hash(hash) => count(rest.chunks(2).filter(|pair| {
+                let field = Field {
+                    value: pair[1].clone(),
+                    expires_at: None,
+                };
+                !hash
+                    .insert(pair[0].clone(), field)
+                    .is_some_and(|old| old.is_live())
+            })),
+            _ => return wrong_type(),
+        },
+        "HGET" => match data.get(&key) {
+            Some(Entry::Hash(hash)) => hash
+                .get(&rest[0])
+                .filter(|field| field.is_live())
+                .map(|field| Value::BulkString(field.value.clone()))
+                .unwrap_or(Value::Nil),
+            None => Value::Nil,
+            _ => return wrong_type(),
+        },
+        "HMGET" => match data.get(&key) {
+            Some(Entry::Hash(hash)) => Value::Array(
+                rest.iter()
+                    .map(|name| {
+                        hash.get(name)
+                            .filter(|field| field.is_live())
+                            .map(|field| Value::BulkString(field.value.clone()))
+                            .unwrap_or(Value::Nil)
+                    })
+                    .collect(),
+            ),
+            None => Value::Array(rest.iter().map(|_| Value::Nil).collect()),
+            _ => return wrong_type(),
+        },
+        "HLEN" => match data.get(&key) {
+            Some(Entry::Hash(hash)) => count(hash.values().filter(|field| field.is_live())),
+            None => Value::Int(0),
+            _ => return wrong_type(),
+        },
+        "HEXISTS" => match data.get(&key) {
+            Some(Entry::Hash(hash)) => {
+                Value::Int(hash.get(&rest[0]).is_some_and(|field| field.is_live()) as i64)
+            }
+            None => Value::Int(0),
+            _ => return wrong_type(),
+        },
+        "HINCRBY" => match data.entry(key).or_insert(Entry::Hash(HashMap::new())) {
+            Entry::Hash(hash) => {
+                let delta = integer(&rest[1])?;
+                let field = hash.entry(rest[0].clone()).or_insert(Field {
+                    value: b"0".to_vec(),
+                    expires_at: None,
+                });
+                if !field.is_live() {
+                    *field = Field {
+                        value: b"0".to_vec(),
+                        expires_at: None,
+                    };
+                }
+                let value = integer(&field.value)?
+                    .checked_add(delta)
+                    .ok_or_else(|| RedisError::from((ErrorKind::TypeError, "Overflow")))?;
+                field.value = value.to_string().into_bytes();
+                Value::Int(value)
+            }
+            _ => return wrong_type(),
+        },
+        "HSCAN" => match data.get(&key) {
+            Some(Entry::Hash(hash)) => {
+                let cursor = integer(&rest[0])? as usize;
+                let page = rest
+                    .windows(2)
+                    .find(|pair| pair[0].eq_ignore_ascii_case(b"COUNT"))
+                    .map(|pair| integer(&pair[1]))
+                    .transpose()?
+                    .unwrap_or(10) as usize;
+
+                // Field names in order are the cursor space, the cursor is an offset in it
+                let mut names = hash
+                    .iter()
+                    .filter(|(_, field)| field.is_live())
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>();
+                names.sort();
+
+                let end = cursor.saturating_add(page).min(names.len());
+                let next = if end < names.len() { end } else { 0 };
+                let fields = names
+                    .get(cursor..end)
+                    .unwrap_or_default()
+                    .iter()
+                    .flat_map(|name| {
+                        [
+                            Value::BulkString((*name).clone()),
+                            Value::BulkString(hash[*name].value.clone()),
+                        ]
+                    })
+                    .collect();
+
+                Value::Array(vec![
+                    Value::BulkString(next.to_string().into_bytes()),
+                    Value::Array(fields),
+                ])
+            }
+            None => Value::Array(vec![Value::BulkString(b"0".to_vec()), Value::Array(vec![])]),
+            _ => return wrong_type(),
+        },
+        "HGETALL" => match data.get(&key) {
+            Some(Entry::Hash(hash)) => Value::Array(
+                hash.iter()
+                    .filter(|(_, field)| field.is_live())
+                    .flat_map(|(name, field)| {
+                        [
+                            Value::BulkString(name.clone()),
+                            Value::BulkString(field.value.clone()),
+                        ]
+                    })
+                    .collect(),
+            ),
+            None => Value::Array(vec![]),
+            _ => return wrong_type(),
+        },
+        "HDEL" => match data.get_mut(&key) {
+            Some(Entry::Hash(hash)) => count(
+                rest.iter()
+                    .filter(|field| hash.remove(*field).is_some_and(|field| field.is_live())),
+            ),
+            None => Value::Int(0),
+            _ => return wrong_type(),
+        },
+        // HPEXPIRE key milliseconds FIELDS count field...
+        "HPEXPIRE" => match data.get_mut(&key) {
+            Some(Entry::Hash(hash)) => {
+                let ttl = integer(&rest[0])? as u64;
+                let fields = rest
+                    .iter()
+                    .skip_while(|arg| !arg.eq_ignore_ascii_case(b"FIELDS"));
+                Value::Array(
+                    fields
+                        .skip(2)
+                        .map(|name| match hash.get_mut(name) {
+                            Some(field) if field.is_live() => {
+                                field.expires_at = Some(now_millis() + ttl);
+                                Value::Int(1)
+                            }
+                            _ => Value::Int(-2),
+                        })
+                        .collect(),
+                )
+            }
+            None => Value::Array(vec![]),
+            _ => return wrong_type(),
+        },
+        // HPERSIST key FIELDS count field...
+        "HPERSIST" => match data.get_mut(&key) {
+            Some(Entry::Hash(hash)) => Value::Array(
+                rest.iter()
+                    .skip(2)
+                    .map(|name| match hash.get_mut(name) {
+                        Some(field) if field.is_live() && field.expires_at.is_some() => {
+                            field.expires_at = None;
+                            Value::Int(1)
+                        }
+                        Some(field) if field.is_live() => Value::Int(-1),
+                        _ => Value::Int(-2),
+                    })
+                    .collect(),
+            ),
+            None => Value::Array(rest.iter().skip(2).map(|_| Value::Int(-2)).collect()),
+            _ => return wrong_type(),
+        },
+        "SADD" => match data.entry(key).or_insert(Entry::Set(HashSet::new())) {
+            Entry::Set(set) => count(rest.iter().filter(|member| set.insert((*member).clone()))),
+            _ => return wrong_type(),
+        },
+        "SREM" => match data.get_mut(&key) {
+            Some(Entry::Set(set)) => count(rest.iter().filter(|member| set.remove(*member))),
+            None => Value::Int(0),
+            _ => return wrong_type(),
+        },
+        "SISMEMBER" => match data.get(&key) {
+            Some(Entry::Set(set)) => Value::Int(set.contains(&rest[0]) as i64),
+            None => Value::Int(0),
+            _ => return wrong_type(),
+        },
+        "SCARD" => match data.get(&key) {
+            Some(Entry::Set(set)) => Value::Int(set.len() as i64),
+            None => Value::Int(0),
+            _ => return wrong_type(),
+        },
+        "SMEMBERS" => match data.get(&key) {
+            Some(Entry::Set(set)) => {
+                Value::Array(set.iter().cloned().map(Value::BulkString).collect())
+            }
+            None => Value::Array(vec![]),
+            _ => return wrong_type(),
+        },
+        "ZADD" => match data.entry(key).or_insert(Entry::SortedSet(BTreeMap::new())) {
+            Entry::SortedSet(set) => {
+                let mut added = 0;
+                for pair in rest.chunks(2) {
+                    if set.insert(pair[1].clone(), score(&pair[0])?).is_none() {
+                        added += 1;
+                    }
+                }
+                Value::Int(added)
+            }
+            _ => return wrong_type(),
+        },
+        "ZREM" => match data.get_mut(&key) {
+            Some(Entry::SortedSet(set)) => {
+                count(rest.iter().filter(|member| set.remove(*member).is_some()))
+            }
+            None => Value::Int(0),
+            _ => return wrong_type(),
+        },
+        "ZRANGEBYSCORE" => match data.get(&key) {
+            Some(Entry::SortedSet(set)) => {
+                let (min, max) = (score(&rest[0])?, score(&rest[1])?);
+                let mut members = set
+                    .iter()
+                    .filter(|(_, score)| (min..=max).contains(*score))
+                    .collect::<Vec<_>>();
+                members.sort_by(|a, b| a.1.total_cmp(b.1).then_with(|| a.0.cmp(b.0)));
+
+                let with_scores = rest
+                    .iter()
+                    .any(|arg| arg.eq_ignore_ascii_case(b"WITHSCORES"));
+                Value::Array(
+                    members
+                        .into_iter()
+                        .flat_map(|(member, score)| {
+                            let score = with_scores
+                                .then(|| Value::BulkString(score.to_string().into_bytes()));
+                            [Some(Value::BulkString(member.clone())), score]
+                        })
+                        .flatten()
+                        .collect(),
+                )
+            }
+            None => Value::Array(vec![]),
+            _ => return wrong_type(),
+        },
+        "SCAN" => {
+            let pattern = args
+                .windows(2)
+                .find(|pair| pair[0].eq_ignore_ascii_case(b"MATCH"))
+                .map(|pair| String::from_utf8_lossy(&pair[1]).to_string())
+                .unwrap_or_else(|| "*".to_string());
+            let keys = data
+                .keys()
+                .filter(|key| glob_match(&pattern, &String::from_utf8_lossy(key)))
+                .cloned()
+                .map(Value::BulkString)
+                .collect();
+            Value::Array(vec![Value::BulkString(b"0".to_vec()), Value::Array(keys)])
+        }
+        _ => {
+            return Err(RedisError::from((
+                ErrorKind::ClientError,
+                "Unsupported command",
+                name,
+            )));
+        }
+    };
+
+    Ok(reply)
+}
+
+/// Elements a command changes, the ones a persistent database writes back.
+/// `None` stands for the whole key
+fn written_items(args: &[Vec<u8>]) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+    let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+    let Some(key) = args.get(1) else {
+        return vec![];
+    };
+    let rest = &args[2..];
+
+    let items: Vec<&Vec<u8>> = match name.as_str() {
+        "DEL" => return args[1..].iter().map(|key| (key.clone(), None)).collect(),
+        "HSET" => rest.iter().step_by(2).collect(),
+        "ZADD" => rest.iter().skip(1).step_by(2).collect(),
+        "HINCRBY" => rest.iter().take(1).collect(),
+        "HDEL" | "SADD" | "SREM" | "ZREM" => rest.iter().collect(),
+        // Field names follow FIELDS and their count
+        "HPEXPIRE" | "HPERSIST" => rest
+            .iter()
+            .skip_while(|arg| !arg.eq_ignore_ascii_case(b"FIELDS"))
+            .skip(2)
+            .collect(),
+        _ => vec![],
+    };
+
+    items
+        .into_iter()
+        .map(|item| (key.clone(), Some(item.clone())))
+        .collect()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_millis() as u64
+}
+
+fn count<T>(items: impl Iterator<Item = T>) -> Value {
+    Value::Int(items.count() as i64)
+}
+
+fn integer(arg: &[u8]) -> RedisResult<i64> {
+    String::from_utf8_lossy(arg)
+        .parse()
+        .map_err(|_| RedisError::from((ErrorKind::TypeError, "Value is not an integer")))
+}
+
+fn score(arg: &[u8]) -> RedisResult<f64> {
+    match String::from_utf8_lossy(arg).as_ref() {
+        "-inf" => Ok(f64::NEG_INFINITY),
+        "+inf" | "inf" => Ok(f64::INFINITY),
+        score => score
+            .parse()
+            .map_err(|_| RedisError::from((ErrorKind::TypeError, "Score is not a number"))),
+    }
+}
+
+fn wrong_type() -> RedisResult<Value> {
+    Err(RedisError::from((
+        ErrorKind::TypeError,
+        "Operation against a key holding the wrong kind of value",
+    )))
+}
+
+/// Glob with `*` wildcards, the only ones the bot uses in patterns
+fn glob_match(pattern: &str, key: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == key,
+        Some((prefix, rest)) => {
+            key.starts_with(prefix)
+                && key[prefix.len()..]
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .chain([key.len() - prefix.len()])
+                    .any(|i| glob_match(rest, &key[prefix.len() + i..]))
+        }
+    }
+}
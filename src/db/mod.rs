@@ -0,0 +1,122 @@
+pub mod giveaways;
+pub mod memory;
+pub mod sqlite;
+
+use crate::errors::AppResult;
+use bb8_redis::RedisConnectionManager;
+use bb8_redis::bb8::{Pool, PooledConnection};
+use giveaways::{GiveawayRepository, MemoryGiveaways, RedisGiveaways};
+use memory::{MemoryConnection, MemoryDb};
+use redis::aio::ConnectionLike;
+use redis::{Cmd, Pipeline, RedisFuture, Value};
+use sqlite::{SqliteGiveaways, SqliteKv};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+
+/// Storage the handlers work with: giveaways go through a `GiveawayRepository`, everything
+/// else (schedules, languages, channels...) through connections. The typed wrappers in
+/// `calls::types` only need `ConnectionLike`, so they work the same on every backend
+#[derive(Clone)]
+pub struct Db {
+    connections: Connections,
+    giveaways: Arc<dyn GiveawayRepository>,
+}
+
+impl Db {
+    pub fn redis(pool: Pool<RedisConnectionManager>) -> Self {
+        let connections = Connections::Redis(pool);
+        Db {
+            giveaways: Arc::new(RedisGiveaways::new(connections.clone())),
+            connections,
+        }
+    }
+
+    /// Nothing outlives the process, for tests and local runs
+    pub fn memory() -> Self {
+        Db {
+            connections: Connections::Memory(MemoryDb::default()),
+            giveaways: Arc::new(MemoryGiveaways::default()),
+        }
+    }
+
+    /// Redis layout of the data on the in-memory database, to test it without a server
+    #[cfg(test)]
+    pub fn redis_in_memory() -> Self {
+        let connections = Connections::Memory(MemoryDb::default());
+        Db {
+            giveaways: Arc::new(RedisGiveaways::new(connections.clone())),
+            connections,
+        }
+    }
+
+    /// Giveaways in SQLite tables, the rest in memory written through to SQLite
+    pub async fn sqlite(pool: SqlitePool) -> AppResult<Self> {
+        let kv = MemoryDb::persistent(SqliteKv::new(pool.clone())).await?;
+        Ok(Db {
+            connections: Connections::Memory(kv),
+            giveaways: Arc::new(SqliteGiveaways::new(pool)),
+        })
+    }
+
+    pub async fn get(&self) -> AppResult<DbConnection> {
+        self.connections.get().await
+    }
+
+    pub fn giveaways(&self) -> &dyn GiveawayRepository {
+        self.giveaways.as_ref()
+    }
+
+    /// Redis keeps the data of older versions of the bot, the other backends start fresh
+    pub fn is_redis(&self) -> bool {
+        matches!(self.connections, Connections::Redis(_))
+    }
+}
+
+#[derive(Clone)]
+enum Connections {
+    Redis(Pool<RedisConnectionManager>),
+    /// Redis commands the bot uses, run on maps in the process
+    Memory(MemoryDb),
+}
+
+impl Connections {
+    async fn get(&self) -> AppResult<DbConnection> {
+        match self {
+            Connections::Redis(pool) => Ok(DbConnection::Redis(pool.get_owned().await?)),
+            Connections::Memory(db) => Ok(DbConnection::Memory(db.connection())),
+        }
+    }
+}
+
+pub enum DbConnection {
+    Redis(PooledConnection<'static, RedisConnectionManager>),
+    Memory(MemoryConnection),
+}
+
+impl ConnectionLike for DbConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            DbConnection::Redis(conn) => conn.req_packed_command(cmd),
+            DbConnection::Memory(conn) => conn.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            DbConnection::Redis(conn) => conn.req_packed_commands(cmd, offset, count),
+            DbConnection::Memory(conn) => conn.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            DbConnection::Redis(conn) => conn.get_db(),
+            DbConnection::Memory(conn) => conn.get_db(),
+        }
+    }
+}
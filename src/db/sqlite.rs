@@ -0,0 +1,349 @@
+use super::giveaways::GiveawayRepository;
+use crate::calls::models::Giveaway;
+use crate::errors::AppResult;
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use teloxide::types::{User, UserId};
+use uuid::Uuid;
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS giveaways (
+        id TEXT PRIMARY KEY,
+        owner_id INTEGER NOT NULL,
+        data TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS giveaways_owner ON giveaways (owner_id)",
+    "CREATE TABLE IF NOT EXISTS participants (
+        giveaway_id TEXT NOT NULL,
+        user_id INTEGER NOT NULL,
+        profile TEXT NOT NULL,
+        PRIMARY KEY (giveaway_id, user_id)
+    )",
+    "CREATE TABLE IF NOT EXISTS kv_items (
+        key BLOB NOT NULL,
+        item BLOB NOT NULL,
+        kind INTEGER NOT NULL,
+        value BLOB,
+        score REAL,
+        expires_at INTEGER,
+        PRIMARY KEY (key, item)
+    )",
+    "CREATE INDEX IF NOT EXISTS kv_items_expires_at ON kv_items (expires_at)",
+];
+
+/// Opens the database file, creating it and the tables if needed
+pub async fn open(path: &str) -> AppResult<SqlitePool> {
+    let pool = SqlitePool::connect(&format!("sqlite:{path}?mode=rwc")).await?;
+
+    for statement in SCHEMA {
+        sqlx::query(statement).execute(&pool).await?;
+    }
+
+    Ok(pool)
+}
+
+/// Giveaways as JSON rows and a row per participant
+pub struct SqliteGiveaways {
+    pool: SqlitePool,
+}
+
+impl SqliteGiveaways {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteGiveaways { pool }
+    }
+}
+
+#[async_trait]
+impl GiveawayRepository for SqliteGiveaways {
+    async fn create(&self, owner_id: u64, id: Uuid, giveaway: Giveaway) -> AppResult<()> {
+        self.update(owner_id, id, giveaway).await
+    }
+
+    async fn get(&self, owner_id: u64, id: Uuid) -> AppResult<Option<Giveaway>> {
+        let row: Option<(String,)> =
+            sqlx::query_as("SELECT data FROM giveaways WHERE id = ? AND owner_id = ?")
+                .bind(id.to_string())
+                .bind(owner_id as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        match row {
+            Some((data,)) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn owner(&self, id: Uuid) -> AppResult<Option<u64>> {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT owner_id FROM giveaways WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|(owner_id,)| owner_id as u64))
+    }
+
+    async fn list(&self, owner_id: u64) -> AppResult<Vec<(Uuid, Giveaway)>> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT id, data FROM giveaways WHERE owner_id = ?")
+                .bind(owner_id as i64)
+                .fetch_all(&self.pool)
+                .await?;
+
+        let mut result = Vec::new();
+        for (id, data) in rows {
+            result.push((Uuid::parse_str(&id)?, serde_json::from_str(&data)?));
+        }
+        Ok(result)
+    }
+
+    async fn update(&self, owner_id: u64, id: Uuid, giveaway: Giveaway) -> AppResult<()> {
+        sqlx::query(
+            "INSERT INTO giveaways (id, owner_id, data) VALUES (?, ?, ?)
+             ON CONFLICT (id) DO UPDATE SET owner_id = excluded.owner_id, data = excluded.data",
+        )
+        .bind(id.to_string())
+        .bind(owner_id as i64)
+        .bind(serde_json::to_string(&giveaway)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Keeps the profile of a returning participant up to date, like the Redis hash does
+    async fn add_participant(&self, id: Uuid, user: &User) -> AppResult<Option<usize>> {
+        let profile = serde_json::to_string(user)?;
+        let mut tx = self.pool.begin().await?;
+
+        let inserted = sqlx::query(
+            "INSERT OR IGNORE INTO participants (giveaway_id, user_id, profile) VALUES (?, ?, ?)",
+        )
+        .bind(id.to_string())
+        .bind(user.id.0 as i64)
+        .bind(&profile)
+        .execute(&mut *tx)
+        .await?;
+
+        let joined = if inserted.rows_affected() > 0 {
+            let (count,): (i64,) =
+                sqlx::query_as("SELECT COUNT(*) FROM participants WHERE giveaway_id = ?")
+                    .bind(id.to_string())
+                    .fetch_one(&mut *tx)
+                    .await?;
+            Some(count as usize)
+        } else {
+            sqlx::query(
+                "UPDATE participants SET profile = ? WHERE giveaway_id = ? AND user_id = ?",
+            )
+            .bind(&profile)
+            .bind(id.to_string())
+            .bind(user.id.0 as i64)
+            .execute(&mut *tx)
+            .await?;
+            None
+        };
+
+        tx.commit().await?;
+        Ok(joined)
+    }
+
+    async fn remove_participant(&self, id: Uuid, user_id: UserId) -> AppResult<()> {
+        sqlx::query("DELETE FROM participants WHERE giveaway_id = ? AND user_id = ?")
+            .bind(id.to_string())
+            .bind(user_id.0 as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn is_participant(&self, id: Uuid, user_id: UserId) -> AppResult<bool> {
+        Ok(self.participant(id, user_id.0).await?.is_some())
+    }
+
+    async fn participant_count(&self, id: Uuid) -> AppResult<usize> {
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM participants WHERE giveaway_id = ?")
+                .bind(id.to_string())
+                .fetch_one(&self.pool)
+                .await?;
+
+        Ok(count as usize)
+    }
+
+    async fn participant(&self, id: Uuid, user_id: u64) -> AppResult<Option<User>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            "SELECT profile FROM participants WHERE giveaway_id = ? AND user_id = ?",
+        )
+        .bind(id.to_string())
+        .bind(user_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some((profile,)) => Ok(Some(serde_json::from_str(&profile)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn participants(&self, id: Uuid) -> AppResult<Vec<User>> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT profile FROM participants WHERE giveaway_id = ? ORDER BY user_id",
+        )
+        .bind(id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut result = Vec::new();
+        for (profile,) in rows {
+            result.push(serde_json::from_str(&profile)?);
+        }
+        Ok(result)
+    }
+}
+
+/// Element of a key of a `MemoryDb`
+pub enum KvItem {
+    /// Hash field, `expires_at` is unix time in milliseconds
+    Field {
+        value: Vec<u8>,
+        expires_at: Option<u64>,
+    },
+    SetMember,
+    SortedSetMember {
+        score: f64,
+    },
+}
+
+/// Key, element, kind, value, score and expiry time of a `kv_items` row
+type KvRow = (
+    Vec<u8>,
+    Vec<u8>,
+    i64,
+    Option<Vec<u8>>,
+    Option<f64>,
+    Option<i64>,
+);
+
+impl KvItem {
+    const FIELD: i64 = 0;
+    const SET_MEMBER: i64 = 1;
+    const SORTED_SET_MEMBER: i64 = 2;
+}
+
+pub enum KvChange {
+    /// Stores the element, replacing the previous one
+    Put {
+        key: Vec<u8>,
+        item: Vec<u8>,
+        value: KvItem,
+    },
+    /// Removes the element, or every element of the key with `None`
+    Remove { key: Vec<u8>, item: Option<Vec<u8>> },
+}
+
+/// Keys of a `MemoryDb` written through to the `kv_items` table a row per element,
+/// so they survive restarts
+pub struct SqliteKv {
+    pool: SqlitePool,
+}
+
+impl SqliteKv {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteKv { pool }
+    }
+
+    /// Every element of every key, expired hash fields are deleted instead
+    pub async fn load(&self) -> AppResult<Vec<(Vec<u8>, Vec<u8>, KvItem)>> {
+        let now = chrono::Utc::now().timestamp_millis();
+
+        sqlx::query("DELETE FROM kv_items WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        let rows: Vec<KvRow> =
+            sqlx::query_as("SELECT key, item, kind, value, score, expires_at FROM kv_items")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(key, item, kind, value, score, expires_at)| {
+                let value = match kind {
+                    KvItem::FIELD => KvItem::Field {
+                        value: value.unwrap_or_default(),
+                        expires_at: expires_at.map(|at| at as u64),
+                    },
+                    KvItem::SET_MEMBER => KvItem::SetMember,
+                    KvItem::SORTED_SET_MEMBER => KvItem::SortedSetMember {
+                        score: score.unwrap_or_default(),
+                    },
+                    _ => {
+                        log::warn!("Skipping an element of unknown kind {kind}");
+                        return None;
+                    }
+                };
+                Some((key, item, value))
+            })
+            .collect())
+    }
+
+    /// Applies the changes in order in one transaction
+    pub async fn save(&self, changes: Vec<KvChange>) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for change in changes {
+            match change {
+                KvChange::Put { key, item, value } => {
+                    let (kind, value, score, expires_at) = match value {
+                        KvItem::Field { value, expires_at } => (
+                            KvItem::FIELD,
+                            Some(value),
+                            None,
+                            expires_at.map(|at| at as i64),
+                        ),
+                        KvItem::SetMember => (KvItem::SET_MEMBER, None, None, None),
+                        KvItem::SortedSetMember { score } => {
+                            (KvItem::SORTED_SET_MEMBER, None, Some(score), None)
+                        }
+                    };
+
+                    sqlx::query(
+                        "INSERT INTO kv_items (key, item, kind, value, score, expires_at)
+                         VALUES (?, ?, ?, ?, ?, ?)
+                         ON CONFLICT (key, item) DO UPDATE SET kind = excluded.kind,
+                            value = excluded.value, score = excluded.score,
+                            expires_at = excluded.expires_at",
+                    )
+                    .bind(key)
+                    .bind(item)
+                    .bind(kind)
+                    .bind(value)
+                    .bind(score)
+                    .bind(expires_at)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                KvChange::Remove {
+                    key,
+                    item: Some(item),
+                } => {
+                    sqlx::query("DELETE FROM kv_items WHERE key = ? AND item = ?")
+                        .bind(key)
+                        .bind(item)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                KvChange::Remove { key, item: None } => {
+                    sqlx::query("DELETE FROM kv_items WHERE key = ?")
+                        .bind(key)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::i18n::{Lang, Text};
 use bb8_redis::RedisConnectionManager;
 use thiserror::Error;
 
@@ -15,6 +16,14 @@ pub enum AppErrors {
     #[error(transparent)]
     InMemStorageError(#[from] teloxide::dispatching::dialogue::InMemStorageError),
     #[error(transparent)]
+    RedisStorageError(
+        #[from] teloxide::dispatching::dialogue::RedisStorageError<std::convert::Infallible>,
+    ),
+    #[error(transparent)]
+    SqliteStorageError(
+        #[from] teloxide::dispatching::dialogue::SqliteStorageError<std::convert::Infallible>,
+    ),
+    #[error(transparent)]
     UuidError(#[from] uuid::Error),
     #[error(transparent)]
     UrlError(#[from] url::ParseError),
@@ -31,10 +40,25 @@ pub enum AppErrors {
     ),
     #[error("{0}")]
     StringError(String),
+    /// Error meant for the user, shown in their language with `localized`
+    #[error("{0}")]
+    TextError(Text),
     #[error(transparent)]
     BoxedError(#[from] Box<dyn std::error::Error + Send + Sync>),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    SqliteError(#[from] sqlx::Error),
+}
+
+impl AppErrors {
+    /// Message for the user, catalog texts are rendered in `lang`
+    pub fn localized(&self, lang: Lang) -> String {
+        match self {
+            AppErrors::TextError(text) => text.render(lang),
+            e => e.to_string(),
+        }
+    }
 }
 
 pub type AppResult<T> = Result<T, AppErrors>;
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+/// Language of the bot messages, chosen per user and, for channel posts, per giveaway
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Lang {
+    #[default]
+    Uk,
+    En,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::Uk, Lang::En];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::Uk => "uk",
+            Lang::En => "en",
+        }
+    }
+
+    /// Parses `uk`, `en` or a regional code such as `en-US`
+    pub fn from_code(code: &str) -> Option<Lang> {
+        let code = code.trim().to_lowercase();
+        let base = code.split(['-', '_']).next().unwrap_or_default();
+
+        Lang::ALL.into_iter().find(|lang| lang.code() == base)
+    }
+
+    /// Language for a Telegram `language_code`, languages without a catalog get English
+    pub fn from_telegram(code: Option<&str>) -> Lang {
+        match code {
+            Some(code) => Lang::from_code(code).unwrap_or(Lang::En),
+            None => Lang::default(),
+        }
+    }
+
+    /// Name of the language in itself, for the language picker
+    pub fn name(self) -> &'static str {
+        match self {
+            Lang::Uk => "Українська",
+            Lang::En => "English",
+        }
+    }
+}
+
+type Catalog = HashMap<&'static str, String>;
+
+static CATALOGS: LazyLock<HashMap<Lang, Catalog>> = LazyLock::new(|| {
+    HashMap::from([
+        (Lang::Uk, parse_catalog(include_str!("../locales/uk.txt"))),
+        (Lang::En, parse_catalog(include_str!("../locales/en.txt"))),
+    ])
+});
+
+/// Parses `key = text` lines, `\n` in the text is a line break, `#` starts a comment line
+fn parse_catalog(source: &'static str) -> Catalog {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match line.split_once(" = ") {
+            Some((key, text)) => Some((key.trim(), text.replace("\\n", "\n"))),
+            None => {
+                log::warn!("Invalid catalog line: {line}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Logs the keys a catalog is missing compared to the default one, such texts fall back
+/// to the default language
+pub fn check_catalogs() {
+    let default = &CATALOGS[&Lang::default()];
+
+    for lang in Lang::ALL {
+        let catalog = &CATALOGS[&lang];
+        for key in default.keys().filter(|key| !catalog.contains_key(*key)) {
+            log::warn!("Catalog {} has no text {key}", lang.code());
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Arg {
+    Plain(String),
+    Text(Text),
+}
+
+/// Text from the catalog with its `{name}` placeholders, rendered once the language is known
+#[derive(Clone, Debug)]
+pub struct Text {
+    key: &'static str,
+    args: Vec<(&'static str, Arg)>,
+}
+
+impl Text {
+    pub fn new(key: &'static str) -> Self {
+        Text { key, args: vec![] }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl Display) -> Self {
+        self.args.push((name, Arg::Plain(value.to_string())));
+        self
+    }
+
+    /// Placeholder filled with another catalog text in the same language
+    pub fn arg_text(mut self, name: &'static str, value: Text) -> Self {
+        self.args.push((name, Arg::Text(value)));
+        self
+    }
+
+    /// Fills the placeholders in one pass, so a value that contains `{name}` is left as it is
+    pub fn render(&self, lang: Lang) -> String {
+        let template = CATALOGS[&lang]
+            .get(self.key)
+            .or_else(|| CATALOGS[&Lang::default()].get(self.key));
+
+        let Some(template) = template else {
+            log::warn!("No text {} in the catalogs", self.key);
+            return self.key.to_string();
+        };
+
+        let mut text = String::with_capacity(template.len());
+        let mut rest = template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+
+            let arg = after.find('}').and_then(|end| {
+                self.args
+                    .iter()
+                    .find(|(name, _)| *name == &after[..end])
+                    .map(|(_, value)| (end, value))
+            });
+
+            match arg {
+                Some((end, value)) => {
+                    match value {
+                        Arg::Plain(value) => text.push_str(value),
+                        Arg::Text(value) => text.push_str(&value.render(lang)),
+                    }
+                    rest = &after[end + 1..];
+                }
+                // Not a placeholder of this text, kept as written
+                None => {
+                    text.push('{');
+                    rest = after;
+                }
+            }
+        }
+        text.push_str(rest);
+
+        text
+    }
+
+    /// Whether `text` is this text in any language, e.g. a reply keyboard button
+    pub fn matches(&self, text: &str) -> bool {
+        Lang::ALL.into_iter().any(|lang| self.render(lang) == text)
+    }
+}
+
+/// Default language rendering, for logs
+impl Display for Text {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(Lang::default()))
+    }
+}
+
+/// Renders a catalog text: `tr!(lang, "key", name = value)`
+macro_rules! tr {
+    ($lang:expr, $key:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::Text::new($key)$(.arg(stringify!($name), $value))*.render($lang)
+    };
+}
+
+pub(crate) use tr;
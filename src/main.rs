@@ -1,51 +1,147 @@
-use crate::errors::AppResult;
-use crate::models::State;
+use crate::db::Db;
+use crate::errors::{AppErrors, AppResult};
+use crate::i18n::Lang;
+use crate::models::{Command, State};
 use crate::utils::{init_logging, schema};
 use bb8_redis::RedisConnectionManager;
 use bb8_redis::bb8::Pool;
 use dotenv::dotenv;
 use std::sync::Arc;
 use teloxide::dispatching::dialogue::serializer::Bincode;
-use teloxide::dispatching::dialogue::{ErasedStorage, RedisStorage, Storage};
+use teloxide::dispatching::dialogue::{
+    ErasedStorage, InMemStorage, RedisStorage, SqliteStorage, Storage,
+};
 use teloxide::prelude::*;
 
 mod calls;
 mod consts;
+mod db;
 mod errors;
+mod i18n;
+mod migrations;
 mod models;
+mod scheduler;
 mod utils;
 
+#[cfg(test)]
+mod tests;
+
 type MyStorage = Arc<ErasedStorage<State>>;
 
+/// Where data is kept, picked with the `STORAGE` variable and for dialogues optionally
+/// with `DIALOGUE_STORAGE`
+#[derive(Clone, Copy)]
+enum Backend {
+    Redis,
+    /// File at `SQLITE_PATH`, for small self-hosted deployments
+    Sqlite,
+    /// Lost on restart
+    Memory,
+}
+
+impl Backend {
+    fn from_env(name: &str) -> AppResult<Option<Self>> {
+        match dotenv::var(name) {
+            Ok(value) => match value.to_lowercase().as_str() {
+                "redis" => Ok(Some(Backend::Redis)),
+                "sqlite" => Ok(Some(Backend::Sqlite)),
+                "memory" => Ok(Some(Backend::Memory)),
+                _ => Err(AppErrors::StringError(format!(
+                    "Unknown {name} {value}, expected redis, sqlite or memory"
+                ))),
+            },
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+fn sqlite_path() -> String {
+    dotenv::var("SQLITE_PATH").unwrap_or_else(|_| "giveaways.sqlite".to_string())
+}
+
+async fn open_db(backend: Backend) -> AppResult<Db> {
+    match backend {
+        Backend::Redis => {
+            let redis_url = dotenv::var("REDIS_URL")?;
+            log::info!("Connecting to Redis at {redis_url}");
+
+            let pool = Pool::builder()
+                .max_size(10)
+                .build(RedisConnectionManager::new(redis_url)?)
+                .await?;
+            Ok(Db::redis(pool))
+        }
+        Backend::Sqlite => {
+            let path = sqlite_path();
+            log::info!("Opening SQLite database {path}");
+
+            Db::sqlite(db::sqlite::open(&path).await?).await
+        }
+        Backend::Memory => {
+            log::warn!("Storing data in memory, it is lost on restart");
+            Ok(Db::memory())
+        }
+    }
+}
+
+async fn open_dialogues(backend: Backend) -> AppResult<MyStorage> {
+    let storage = match backend {
+        Backend::Redis => RedisStorage::open(&dotenv::var("REDIS_URL")?, Bincode)
+            .await?
+            .erase(),
+        Backend::Sqlite => SqliteStorage::open(&sqlite_path(), Bincode).await?.erase(),
+        Backend::Memory => InMemStorage::new().erase(),
+    };
+
+    Ok(storage)
+}
+
+/// Command menu of Telegram in every catalog language, the default one for other app languages
+async fn set_command_menus(bot: &Bot) {
+    let menus = Lang::ALL
+        .into_iter()
+        .map(|lang| (Some(lang.code()), lang))
+        .chain([(None, Lang::default())]);
+
+    for (code, lang) in menus {
+        let request = bot.set_my_commands(Command::localized(lang));
+        let set = match code {
+            Some(code) => request.language_code(code).await,
+            None => request.await,
+        };
+
+        if let Err(e) = set {
+            log::warn!("Cannot set the command menu for {code:?}: {e}");
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> AppResult<()> {
     dotenv().ok();
     init_logging()?;
+    i18n::check_catalogs();
     log::info!("Starting giveaway bot...");
 
     let bot = Bot::new(dotenv::var("TELOXIDE_TOKEN")?);
-    let redis_url = dotenv::var("REDIS_URL")?;
 
-    log::info!("Connecting to Redis at {redis_url}");
+    let backend = Backend::from_env("STORAGE")?.unwrap_or(Backend::Redis);
+    let db = open_db(backend).await?;
 
-    let redis_pool = Pool::builder()
-        .max_size(10)
-        .build(RedisConnectionManager::new(redis_url.clone())?)
-        .await?;
+    if db.is_redis() {
+        migrations::run(db.clone()).await?;
+    }
 
     let state = Arc::new(State::Start);
 
-    let storage: MyStorage = RedisStorage::open(&redis_url, Bincode)
-        .await
-        .expect("Cannot open redis storage")
-        .erase();
+    let dialogue_backend = Backend::from_env("DIALOGUE_STORAGE")?.unwrap_or(backend);
+    let storage = open_dialogues(dialogue_backend).await?;
+
+    set_command_menus(&bot).await;
+    tokio::spawn(scheduler::run(bot.clone(), db.clone()));
 
     Dispatcher::builder(bot, schema())
-        .dependencies(dptree::deps![
-            Arc::clone(&state),
-            redis_pool.clone(),
-            storage
-        ])
+        .dependencies(dptree::deps![Arc::clone(&state), db.clone(), storage])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
@@ -0,0 +1,69 @@
+use crate::calls::eligibility::record_wins;
+use crate::calls::giveaway_keys;
+use crate::calls::models::{GiveawayIndex, GiveawaysStorage, Participants};
+use crate::consts::{GIVEAWAY_INDEX_KEY, USER_GIVEAWAY_KEY};
+use crate::db::Db;
+use crate::errors::AppResult;
+
+/// Rewrites every stored giveaway in place to the current schema version,
+/// adds giveaways created before the global index to it and their winners to the recent ones.
+///
+/// Old records stay readable, so the bot keeps working if this is interrupted.
+pub async fn run(pool: Db) -> AppResult<()> {
+    let keys = giveaway_keys(pool.clone()).await?;
+
+    let mut conn = pool.get().await?;
+    let mut migrated = 0;
+
+    for key in keys {
+        let owner_id = match key
+            .strip_prefix(USER_GIVEAWAY_KEY)
+            .and_then(|id| id.parse::<u64>().ok())
+        {
+            Some(owner_id) => owner_id,
+            None => {
+                log::warn!("Skipping {key}, it is not a giveaway list");
+                continue;
+            }
+        };
+
+        let giveaways = match GiveawaysStorage::new(key.clone(), &mut conn)
+            .get_all()
+            .await
+        {
+            Ok(giveaways) => giveaways,
+            Err(e) => {
+                log::error!("Cannot read giveaways from {key} for migration: {e}");
+                continue;
+            }
+        };
+
+        for (id, mut giveaway) in giveaways {
+            let mut index = GiveawayIndex::new(GIVEAWAY_INDEX_KEY.to_string(), &mut conn);
+            if !index.contains(id).await? {
+                index.insert(id, owner_id, None).await?;
+            }
+
+            // Wins from before the winners were recorded separately
+            if let Some(ended_at) = giveaway.ended_at {
+                record_wins(&pool, owner_id, giveaway.get_winners(), ended_at).await?;
+            }
+
+            // Participants go first, so an interrupted run keeps them in the old record
+            let mut participants = Participants::new(id, &mut conn);
+            for user in giveaway.take_legacy_participants() {
+                participants.add(&user).await?;
+            }
+
+            if giveaway.migrate() {
+                let mut storage = GiveawaysStorage::new(key.clone(), &mut conn);
+                storage.insert(id, giveaway, None).await?;
+                migrated += 1;
+            }
+        }
+    }
+
+    log::info!("Migrated {migrated} giveaways to the current schema");
+
+    Ok(())
+}
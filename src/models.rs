@@ -1,20 +1,55 @@
+use crate::calls::roles::Role;
+use crate::i18n::{Lang, Text};
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
 use teloxide::dispatching::dialogue::ErasedStorage;
-use teloxide::prelude::Dialogue;
+use teloxide::prelude::{Dialogue, Message};
+use teloxide::types::BotCommand;
 use teloxide::utils::command::BotCommands;
+use uuid::Uuid;
 
 pub type MyDialogue = Dialogue<State, ErasedStorage<State>>;
 
+/// Descriptions are in the catalogs, see `Command::localized`
 #[derive(BotCommands, Clone)]
-#[command(rename_rule = "lowercase", description = "Доступні команди:")]
+#[command(rename_rule = "lowercase")]
 pub enum Command {
-    #[command(description = "Показує доступні команди.")]
     Help,
-    #[command(description = "Запускає бота.")]
     Start(String),
-    #[command(description = "Нічого не робить.")]
     Cancel,
+    Verify(String),
+    Archive(String),
+    Language(String),
+    Postlang(String),
+}
+
+impl Command {
+    /// Commands with their descriptions in `lang`, for /help and the command menu
+    pub fn localized(lang: Lang) -> Vec<BotCommand> {
+        Command::bot_commands()
+            .into_iter()
+            .filter_map(|command| {
+                let description = Command::description(&command.command)?.render(lang);
+                Some(BotCommand::new(
+                    command.command.trim_start_matches('/'),
+                    description,
+                ))
+            })
+            .collect()
+    }
+
+    fn description(command: &str) -> Option<Text> {
+        let key = match command {
+            "/help" => "command.help",
+            "/start" => "command.start",
+            "/cancel" => "command.cancel",
+            "/verify" => "command.verify",
+            "/archive" => "command.archive",
+            "/language" => "command.language",
+            "/postlang" => "command.postlang",
+            _ => return None,
+        };
+        Some(Text::new(key))
+    }
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -29,60 +64,211 @@ pub enum State {
     RerollOrEnd,
     List,
     ShowParticipants,
+    SetEndTime {
+        id: Uuid,
+    },
+    SchedulePublication,
+    SetPartnerChannels,
+    SetRules,
+    SetPrizes {
+        id: Uuid,
+    },
+    ChooseRole,
+    GrantRole {
+        id: Uuid,
+        role: Option<Role>,
+    },
+    PickChannel {
+        id: Uuid,
+    },
+    SavedChannels,
+    PickGiveaway {
+        channel: String,
+    },
+    EditGiveaway,
+    EditContent {
+        id: Uuid,
+    },
 }
 
+/// Button of a reply keyboard. Handlers are routed by the stable id of the button, its label
+/// is only looked up in the registry of all the labels it has in the catalogs
+pub trait MenuButton: Copy + Sized + 'static {
+    /// Buttons in the order they are shown
+    const ALL: &'static [Self];
+
+    /// Identifier of the action, independent of the label and the language
+    fn id(self) -> &'static str;
+
+    fn text(self) -> Text;
+
+    /// Button with `label` in any language, ignoring case, emoji and spacing
+    fn from_label(label: &str) -> Option<Self> {
+        let label = normalize_label(label);
+        if label.is_empty() {
+            return None;
+        }
+
+        Self::ALL.iter().copied().find(|button| {
+            Lang::ALL
+                .into_iter()
+                .any(|lang| normalize_label(&button.text().render(lang)) == label)
+        })
+    }
+}
+
+/// Letters and digits of a label in lowercase, separated by single spaces
+pub fn normalize_label(label: &str) -> String {
+    label
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MenuCommands {
     CreateGiveaway,
     CancelGiveaway,
     GiveawayList,
     AddGroupId,
-    DoNothing,
+    SchedulePublication,
+    ScheduledPublications,
+    PartnerChannels,
+    EligibilityRules,
+    EndGiveaway,
+    GiveawayRoles,
+    SavedChannels,
+    PauseGiveaway,
+    ResumeGiveaway,
+    EditGiveaway,
 }
 
-impl Display for MenuCommands {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl MenuButton for MenuCommands {
+    const ALL: &'static [MenuCommands] = &[
+        MenuCommands::CreateGiveaway,
+        MenuCommands::CancelGiveaway,
+        MenuCommands::GiveawayList,
+        MenuCommands::AddGroupId,
+        MenuCommands::SchedulePublication,
+        MenuCommands::ScheduledPublications,
+        MenuCommands::PartnerChannels,
+        MenuCommands::EligibilityRules,
+        MenuCommands::EndGiveaway,
+        MenuCommands::GiveawayRoles,
+        MenuCommands::SavedChannels,
+        MenuCommands::PauseGiveaway,
+        MenuCommands::ResumeGiveaway,
+        MenuCommands::EditGiveaway,
+    ];
+
+    fn id(self) -> &'static str {
         match self {
-            MenuCommands::CreateGiveaway => write!(f, "Створити розіграш"),
-            MenuCommands::CancelGiveaway => write!(f, "Скасувати розіграш"),
-            MenuCommands::GiveawayList => write!(f, "Список розіграшів"),
-            MenuCommands::AddGroupId => write!(f, "Додати розіграш в групу"),
-            MenuCommands::DoNothing => write!(f, "Do nothing"),
+            MenuCommands::CreateGiveaway => "create",
+            MenuCommands::CancelGiveaway => "cancel",
+            MenuCommands::GiveawayList => "list",
+            MenuCommands::AddGroupId => "add_group",
+            MenuCommands::SchedulePublication => "schedule",
+            MenuCommands::ScheduledPublications => "scheduled",
+            MenuCommands::PartnerChannels => "partners",
+            MenuCommands::EligibilityRules => "rules",
+            MenuCommands::EndGiveaway => "end",
+            MenuCommands::GiveawayRoles => "roles",
+            MenuCommands::SavedChannels => "channels",
+            MenuCommands::PauseGiveaway => "pause",
+            MenuCommands::ResumeGiveaway => "resume",
+            MenuCommands::EditGiveaway => "edit",
         }
     }
-}
 
-impl From<String> for MenuCommands {
-    fn from(s: String) -> Self {
-        match s.as_str() {
-            "Створити розіграш" => MenuCommands::CreateGiveaway,
-            "Скасувати розіграш" => MenuCommands::CancelGiveaway,
-            "Список розіграшів" => MenuCommands::GiveawayList,
-            "Додати розіграш в групу" => MenuCommands::AddGroupId,
-            _ => MenuCommands::DoNothing,
+    fn text(self) -> Text {
+        match self {
+            MenuCommands::CreateGiveaway => Text::new("menu.create"),
+            MenuCommands::CancelGiveaway => Text::new("menu.cancel"),
+            MenuCommands::GiveawayList => Text::new("menu.list"),
+            MenuCommands::AddGroupId => Text::new("menu.add_group"),
+            MenuCommands::SchedulePublication => Text::new("menu.schedule"),
+            MenuCommands::ScheduledPublications => Text::new("menu.scheduled"),
+            MenuCommands::PartnerChannels => Text::new("menu.partners"),
+            MenuCommands::EligibilityRules => Text::new("menu.rules"),
+            MenuCommands::EndGiveaway => Text::new("menu.end"),
+            MenuCommands::GiveawayRoles => Text::new("menu.roles"),
+            MenuCommands::SavedChannels => Text::new("menu.channels"),
+            MenuCommands::PauseGiveaway => Text::new("menu.pause"),
+            MenuCommands::ResumeGiveaway => Text::new("menu.resume"),
+            MenuCommands::EditGiveaway => Text::new("menu.edit"),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ListCommands {
     ShowParticipants,
     Return,
 }
 
-impl Display for ListCommands {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl MenuButton for ListCommands {
+    const ALL: &'static [ListCommands] = &[ListCommands::ShowParticipants, ListCommands::Return];
+
+    fn id(self) -> &'static str {
         match self {
-            ListCommands::ShowParticipants => write!(f, "Показати учасників"),
-            ListCommands::Return => write!(f, "Повернутись назад"),
+            ListCommands::ShowParticipants => "show_participants",
+            ListCommands::Return => "return",
         }
     }
+
+    fn text(self) -> Text {
+        match self {
+            ListCommands::ShowParticipants => Text::new("list.show_participants"),
+            ListCommands::Return => Text::new("list.return"),
+        }
+    }
+}
+
+/// Button pressed on a reply keyboard of type `B`, for routing in the `schema`
+pub fn pressed_button<B: MenuButton>(msg: Message) -> Option<B> {
+    let button = B::from_label(msg.text()?)?;
+    log::debug!(
+        "Button {} pressed by {:?}",
+        button.id(),
+        msg.from.map(|user| user.id)
+    );
+    Some(button)
 }
 
-impl From<String> for ListCommands {
-    fn from(s: String) -> Self {
-        match s.as_str() {
-            "Показати учасників" => ListCommands::ShowParticipants,
-            "Повернутись назад" => ListCommands::Return,
-            _ => ListCommands::Return,
+/// What a giveaway picked from an inline keyboard is used for, encoded in the callback data
+#[derive(Clone, Copy)]
+pub enum GiveawayAction {
+    Cancel,
+    ShowParticipants,
+    Publish,
+    Pause,
+    Resume,
+    Edit,
+}
+
+impl GiveawayAction {
+    pub fn code(&self) -> &'static str {
+        match self {
+            GiveawayAction::Cancel => "c",
+            GiveawayAction::ShowParticipants => "v",
+            GiveawayAction::Publish => "p",
+            GiveawayAction::Pause => "z",
+            GiveawayAction::Resume => "r",
+            GiveawayAction::Edit => "e",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "c" => Some(GiveawayAction::Cancel),
+            "v" => Some(GiveawayAction::ShowParticipants),
+            "p" => Some(GiveawayAction::Publish),
+            "z" => Some(GiveawayAction::Pause),
+            "r" => Some(GiveawayAction::Resume),
+            "e" => Some(GiveawayAction::Edit),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,218 @@
+use crate::calls::language::user_lang;
+use crate::calls::models::{ScheduleStorage, ScheduledGiveaway};
+use crate::calls::status::GiveawayStatus;
+use crate::calls::{draw_and_announce, post_announcement, publish_giveaway};
+use crate::consts::{
+    ANNOUNCE_GIVE_UP_SECS, ANNOUNCE_RETRY_SECS, ANNOUNCE_SCHEDULE_KEY, END_SCHEDULE_KEY,
+    PUBLISH_SCHEDULE_KEY, SCHEDULER_INTERVAL_SECS,
+};
+use crate::db::Db;
+use crate::errors::AppResult;
+use crate::i18n::tr;
+use std::time::Duration;
+use teloxide::Bot;
+use teloxide::prelude::{ChatId, Requester};
+
+/// Periodically publishes scheduled giveaways, ends giveaways whose deadline has passed
+/// and retries the channel announcements of results that failed.
+///
+/// Deadlines live in sorted sets of the database, so nothing is lost on restart.
+pub async fn run(bot: Bot, pool: Db) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SCHEDULER_INTERVAL_SECS));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = publish_due_giveaways(bot.clone(), pool.clone()).await {
+            log::error!("Scheduler failed to publish due giveaways: {e}");
+        }
+
+        if let Err(e) = end_due_giveaways(bot.clone(), pool.clone()).await {
+            log::error!("Scheduler failed to end due giveaways: {e}");
+        }
+
+        if let Err(e) = retry_announcements(bot.clone(), pool.clone()).await {
+            log::error!("Scheduler failed to retry announcements: {e}");
+        }
+    }
+}
+
+pub async fn end_due_giveaways(bot: Bot, pool: Db) -> AppResult<()> {
+    let now = chrono::Utc::now().timestamp();
+
+    let due = {
+        let mut conn = pool.get().await?;
+        let mut schedule = ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn);
+        schedule.range_to(now).await?
+    };
+
+    for (scheduled, _) in due {
+        let ScheduledGiveaway { owner, id } = scheduled.clone();
+
+        log::info!("Giveaway {id} of user {owner} is due to end");
+
+        match pool.giveaways().get(owner, id).await? {
+            Some(giveaway) if giveaway.status().can_become(GiveawayStatus::Ended) => {
+                let count = giveaway.winners_to_draw(giveaway.winners_count);
+                let owner_chat = ChatId(owner as i64);
+                let lang = user_lang(&pool, &giveaway.owner).await;
+                if let Err(e) = draw_and_announce(
+                    pool.clone(),
+                    bot.clone(),
+                    owner_chat,
+                    owner,
+                    id,
+                    giveaway,
+                    count,
+                    lang,
+                )
+                .await
+                {
+                    log::error!("Cannot end giveaway {id}: {e}");
+                }
+            }
+            giveaway => {
+                let mut conn = pool.get().await?;
+                let mut schedule = ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn);
+                schedule.remove(scheduled).await?;
+
+                // E.g. still a draft at the deadline, the owner has to know it didn't end
+                let Some(giveaway) = giveaway else { continue };
+                let Err(e) = giveaway.check_transition(GiveawayStatus::Ended) else {
+                    continue;
+                };
+                log::warn!("Cannot end giveaway {id} on schedule: {e}");
+
+                let lang = user_lang(&pool, &giveaway.owner).await;
+                let notice = tr!(
+                    lang,
+                    "end.schedule_failed",
+                    id = id,
+                    error = e.localized(lang)
+                );
+                if let Err(e) = bot.send_message(ChatId(owner as i64), notice).await {
+                    log::warn!("Cannot tell {owner} that giveaway {id} didn't end: {e}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn retry_announcements(bot: Bot, pool: Db) -> AppResult<()> {
+    let now = chrono::Utc::now().timestamp();
+
+    let due = {
+        let mut conn = pool.get().await?;
+        let mut schedule = ScheduleStorage::new(ANNOUNCE_SCHEDULE_KEY.to_string(), &mut conn);
+        schedule.range_to(now).await?
+    };
+
+    for (scheduled, _) in due {
+        let ScheduledGiveaway { owner, id } = scheduled.clone();
+
+        let mut giveaway = match pool.giveaways().get(owner, id).await? {
+            Some(giveaway) if giveaway.announcement_pending => giveaway,
+            _ => {
+                let mut conn = pool.get().await?;
+                let mut schedule =
+                    ScheduleStorage::new(ANNOUNCE_SCHEDULE_KEY.to_string(), &mut conn);
+                schedule.remove(scheduled).await?;
+                continue;
+            }
+        };
+
+        log::info!("Retrying the announcement of giveaway {id}");
+
+        let error = match post_announcement(&pool, &bot, owner, id, &mut giveaway).await {
+            Ok(()) => continue,
+            Err(e) => e,
+        };
+
+        let mut conn = pool.get().await?;
+        let mut schedule = ScheduleStorage::new(ANNOUNCE_SCHEDULE_KEY.to_string(), &mut conn);
+
+        let ended_at = giveaway.ended_at.unwrap_or(now);
+        if now - ended_at < ANNOUNCE_GIVE_UP_SECS {
+            log::warn!("Cannot announce winners of giveaway {id}, will retry: {error}");
+            schedule
+                .insert(scheduled, now + ANNOUNCE_RETRY_SECS)
+                .await?;
+            continue;
+        }
+
+        log::error!("Giving up on the announcement of giveaway {id}: {error}");
+        schedule.remove(scheduled).await?;
+
+        giveaway.announcement_pending = false;
+        pool.giveaways().update(owner, id, giveaway.clone()).await?;
+
+        let lang = user_lang(&pool, &giveaway.owner).await;
+        bot.send_message(
+            ChatId(owner as i64),
+            tr!(
+                lang,
+                "winners.announce_failed",
+                id = id,
+                error = error.localized(lang)
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn publish_due_giveaways(bot: Bot, pool: Db) -> AppResult<()> {
+    let now = chrono::Utc::now().timestamp();
+
+    let due = {
+        let mut conn = pool.get().await?;
+        let mut schedule = ScheduleStorage::new(PUBLISH_SCHEDULE_KEY.to_string(), &mut conn);
+        schedule.range_to(now).await?
+    };
+
+    for (scheduled, _) in due {
+        let ScheduledGiveaway { owner, id } = scheduled.clone();
+
+        log::info!("Giveaway {id} of user {owner} is due to be published");
+
+        if let Some(mut giveaway) = pool.giveaways().get(owner, id).await?
+            && let Some(publication) = giveaway.publication.clone()
+        {
+            let owner_chat = ChatId(owner as i64);
+            let lang = user_lang(&pool, &giveaway.owner).await;
+            let mut published = giveaway.clone();
+
+            match publish_giveaway(bot.clone(), id, &mut published, publication.channel).await {
+                Ok(m) => {
+                    pool.giveaways().update(owner, id, published).await?;
+
+                    let link = m.url().map(|url| url.to_string()).unwrap_or_default();
+
+                    bot.send_message(owner_chat, tr!(lang, "publish.done", id = id, link = link))
+                        .await?;
+                }
+                Err(e) => {
+                    log::error!("Cannot publish giveaway {id}: {e}");
+
+                    giveaway.cancel_publication();
+                    pool.giveaways().update(owner, id, giveaway).await?;
+
+                    bot.send_message(
+                        owner_chat,
+                        tr!(lang, "publish.failed", id = id, error = e.localized(lang)),
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        let mut conn = pool.get().await?;
+        let mut schedule = ScheduleStorage::new(PUBLISH_SCHEDULE_KEY.to_string(), &mut conn);
+        schedule.remove(scheduled).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use super::Chat;
+use crate::calls::models::{MediaKind, ScheduleStorage};
+use crate::calls::roles::Role;
+use crate::calls::status::GiveawayStatus;
+use crate::consts::END_SCHEDULE_KEY;
+use crate::i18n::{Lang, Text};
+use crate::models::{MenuButton, MenuCommands, State};
+use crate::utils::short_id;
+
+/// Opens the main menu and presses "Create giveaway"
+async fn start_creating(chat: &Chat, lang: Lang) {
+    chat.send("/start").await.expect("Start succeeds");
+    assert!(chat.replied("start").await);
+
+    let label = MenuCommands::CreateGiveaway.text().render(lang);
+    chat.send(&label).await.expect("Menu succeeds");
+    assert!(matches!(chat.state().await, Some(State::CreateGiveaway)));
+    assert!(chat.keyboard_removed().await);
+}
+
+#[tokio::test]
+async fn giveaway_is_created_step_by_step() {
+    let chat = Chat::new(Lang::Uk).await;
+    start_creating(&chat, Lang::Uk).await;
+
+    chat.send_photo("photo-1", Some("Розіграш iPhone"), None)
+        .await
+        .expect("Post is saved");
+
+    let giveaways = chat.giveaways().await;
+    assert_eq!(giveaways.len(), 1);
+    let (id, giveaway) = &giveaways[0];
+    assert_eq!(giveaway.get_text(), "Розіграш iPhone");
+    assert_eq!(giveaway.owner.id.0, 42);
+    assert_eq!(giveaway.status(), GiveawayStatus::Draft);
+    assert_eq!(giveaway.media().len(), 1);
+    assert_eq!(giveaway.media()[0].kind, MediaKind::Photo);
+    assert_eq!(giveaway.media()[0].file_id, "photo-1");
+
+    let done = Text::new("create.done").arg("id", id).render(Lang::Uk);
+    assert!(chat.reply_texts().await.contains(&done));
+    assert!(
+        matches!(chat.state().await, Some(State::SetEndTime { id: state_id }) if state_id == *id)
+    );
+
+    chat.send("01.01.2099 12:00 3")
+        .await
+        .expect("End time is saved");
+    assert!(matches!(chat.state().await, Some(State::SetPrizes { .. })));
+
+    let mut conn = chat.db.get().await.expect("Connection");
+    let scheduled = ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn)
+        .range_to(i64::MAX)
+        .await
+        .expect("Schedule is stored");
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].0.id, *id);
+
+    chat.send("Консоль - 1\nСертифікат - 2")
+        .await
+        .expect("Prizes are saved");
+    assert!(matches!(chat.state().await, Some(State::StartedWindow)));
+
+    let (_, giveaway) = chat.giveaways().await.remove(0);
+    assert_eq!(giveaway.winners_count, 3);
+    assert_eq!(giveaway.prizes.len(), 2);
+    assert_eq!(giveaway.prizes[1].title, "Сертифікат");
+    assert_eq!(chat.keyboard().await.len(), MenuCommands::ALL.len());
+}
+
+#[tokio::test]
+async fn giveaway_without_end_time_and_prizes() {
+    let chat = Chat::new(Lang::En).await;
+    start_creating(&chat, Lang::En).await;
+
+    chat.send("Just text").await.expect("Post is saved");
+    assert_eq!(chat.keyboard().await, ["No end time"]);
+
+    chat.send("No end time").await.expect("End time is skipped");
+    assert!(chat.replied("end_time.unlimited").await);
+    assert_eq!(chat.keyboard().await, ["Skip"]);
+
+    chat.send("Skip").await.expect("Prizes are skipped");
+    assert!(chat.replied("prizes.skipped").await);
+    assert!(matches!(chat.state().await, Some(State::StartedWindow)));
+
+    let (_, giveaway) = chat.giveaways().await.remove(0);
+    assert!(giveaway.media().is_empty());
+    assert!(giveaway.prizes.is_empty());
+    assert_eq!(giveaway.lang, Lang::En);
+}
+
+#[tokio::test]
+async fn album_files_go_to_one_giveaway() {
+    let chat = Chat::new(Lang::Uk).await;
+    start_creating(&chat, Lang::Uk).await;
+
+    chat.send_photo("photo-1", Some("Альбом"), Some("album"))
+        .await
+        .expect("First file is saved");
+    chat.send_photo("photo-2", None, Some("album"))
+        .await
+        .expect("Second file is added");
+
+    let giveaways = chat.giveaways().await;
+    assert_eq!(giveaways.len(), 1);
+
+    let files = giveaways[0]
+        .1
+        .media()
+        .into_iter()
+        .map(|media| media.file_id)
+        .collect::<Vec<_>>();
+    assert_eq!(files, ["photo-1", "photo-2"]);
+}
+
+#[tokio::test]
+async fn caption_over_the_limit_is_rejected() {
+    let chat = Chat::new(Lang::En).await;
+    start_creating(&chat, Lang::En).await;
+
+    let caption = "a".repeat(1000);
+    chat.send_photo("photo-1", Some(&caption), None)
+        .await
+        .expect("Post is rejected");
+
+    assert!(chat.giveaways().await.is_empty());
+    assert!(matches!(chat.state().await, Some(State::CreateGiveaway)));
+}
+
+#[tokio::test]
+async fn prizes_that_overflow_the_post_are_not_saved() {
+    let chat = Chat::new(Lang::En).await;
+    start_creating(&chat, Lang::En).await;
+
+    chat.send(&"a".repeat(3900)).await.expect("Post is saved");
+    chat.send("No end time").await.expect("End time is skipped");
+
+    let prize = "b".repeat(200);
+    chat.send(&format!("{prize} - 1"))
+        .await
+        .expect("Prizes are rejected");
+
+    let (_, giveaway) = chat.giveaways().await.remove(0);
+    assert!(giveaway.prizes.is_empty());
+    assert!(matches!(chat.state().await, Some(State::StartedWindow)));
+}
+
+#[tokio::test]
+async fn giveaway_is_cancelled_from_the_picker() {
+    let chat = Chat::new(Lang::En).await;
+    start_creating(&chat, Lang::En).await;
+    chat.send("To be cancelled").await.expect("Post is saved");
+    let (id, _) = chat.giveaways().await.remove(0);
+
+    chat.set_state(State::StartedWindow).await;
+    let label = MenuCommands::CancelGiveaway.text().render(Lang::En);
+    chat.send(&label).await.expect("Picker is shown");
+    assert!(matches!(chat.state().await, Some(State::CancelGiveaway)));
+
+    let pick = format!("gs:c:{}", short_id(id));
+    assert_eq!(chat.inline_buttons().await, vec![pick.clone()]);
+
+    chat.press_inline(&pick)
+        .await
+        .expect("Cancellation is asked");
+    assert_eq!(chat.calls("answerCallbackQuery").await.len(), 1);
+    let confirm = format!("cc:{}", short_id(id));
+    assert!(chat.inline_buttons().await.contains(&confirm));
+
+    chat.press_inline(&confirm)
+        .await
+        .expect("Giveaway is cancelled");
+    assert!(chat.replied("cancel.done").await);
+
+    let (_, giveaway) = chat.giveaways().await.remove(0);
+    assert_eq!(giveaway.status(), GiveawayStatus::Cancelled);
+}
+
+#[tokio::test]
+async fn menu_button_is_not_saved_as_a_post() {
+    let chat = Chat::new(Lang::Uk).await;
+    start_creating(&chat, Lang::Uk).await;
+
+    let label = MenuCommands::GiveawayList.text().render(Lang::Uk);
+    chat.send(&label).await.expect("Menu succeeds");
+
+    assert!(chat.giveaways().await.is_empty());
+    assert!(chat.replied("list.empty").await);
+    assert!(matches!(chat.state().await, Some(State::StartedWindow)));
+}
+
+#[tokio::test]
+async fn menu_button_does_not_replace_the_edited_text() {
+    let chat = Chat::new(Lang::En).await;
+    start_creating(&chat, Lang::En).await;
+    chat.send("Original").await.expect("Post is saved");
+    let (id, _) = chat.giveaways().await.remove(0);
+
+    chat.set_state(State::StartedWindow).await;
+    chat.press_inline(&format!("gs:e:{}", short_id(id)))
+        .await
+        .expect("Edit is asked");
+    assert!(matches!(
+        chat.state().await,
+        Some(State::EditContent { .. })
+    ));
+    assert!(chat.keyboard_removed().await);
+
+    let label = MenuCommands::CreateGiveaway.text().render(Lang::En);
+    chat.send(&label).await.expect("Menu succeeds");
+    assert!(matches!(chat.state().await, Some(State::CreateGiveaway)));
+
+    let (_, giveaway) = chat.giveaways().await.remove(0);
+    assert_eq!(giveaway.get_text(), "Original");
+}
+
+#[tokio::test]
+async fn invalid_id_leaves_the_edit_step() {
+    let chat = Chat::new(Lang::En).await;
+
+    chat.set_state(State::EditGiveaway).await;
+    chat.send("not an id").await.expect("Reply is sent");
+
+    assert!(chat.replied("id.invalid").await);
+    assert!(matches!(chat.state().await, Some(State::StartedWindow)));
+    assert_eq!(chat.keyboard().await.len(), MenuCommands::ALL.len());
+}
+
+#[tokio::test]
+async fn unknown_user_leaves_the_role_step() {
+    let chat = Chat::new(Lang::En).await;
+    let id = uuid::Uuid::new_v4();
+
+    chat.set_state(State::GrantRole {
+        id,
+        role: Some(Role::Editor),
+    })
+    .await;
+    chat.send("@nobody").await.expect("Reply is sent");
+
+    assert!(chat.replied("roles.user_not_found").await);
+    assert!(matches!(chat.state().await, Some(State::StartedWindow)));
+    assert_eq!(chat.keyboard().await.len(), MenuCommands::ALL.len());
+}
+
+#[tokio::test]
+async fn menu_button_leaves_the_role_step() {
+    let chat = Chat::new(Lang::En).await;
+
+    chat.set_state(State::GrantRole {
+        id: uuid::Uuid::new_v4(),
+        role: None,
+    })
+    .await;
+    let label = MenuCommands::CreateGiveaway.text().render(Lang::En);
+    chat.send(&label).await.expect("Menu succeeds");
+
+    assert!(matches!(chat.state().await, Some(State::CreateGiveaway)));
+}
+
+#[tokio::test]
+async fn mistyped_id_leaves_the_publish_step() {
+    let chat = Chat::new(Lang::En).await;
+
+    for input in ["@channel not-an-id", "@channel 42 extra"] {
+        chat.set_state(State::AddGroupId).await;
+        chat.send(input).await.expect("Input is answered");
+
+        assert!(chat.replied("id.invalid").await, "{input}");
+        assert!(matches!(chat.state().await, Some(State::StartedWindow)));
+    }
+}
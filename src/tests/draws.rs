@@ -0,0 +1,292 @@
+use super::{Chat, USER_ID};
+use crate::calls::eligibility::{Eligibility, EligibilityRule, JoinContext};
+use crate::calls::models::{Giveaway, MessageRef, ScheduleStorage, ScheduledGiveaway};
+use crate::calls::status::GiveawayStatus;
+use crate::calls::{draw_and_announce, post_announcement, write_participant};
+use crate::consts::{ANNOUNCE_SCHEDULE_KEY, END_SCHEDULE_KEY};
+use crate::i18n::{Lang, Text};
+use crate::scheduler::end_due_giveaways;
+use crate::utils::short_id;
+use serde_json::json;
+use std::time::Duration;
+use teloxide::types::{CallbackQuery, ChatId, User};
+use uuid::Uuid;
+
+const CHANNEL_ID: i64 = -100;
+
+fn user(id: u64) -> User {
+    serde_json::from_value(json!({"id": id, "is_bot": false, "first_name": "User"})).expect("User")
+}
+
+/// Active giveaway posted in `CHANNEL_ID` with one participant
+async fn published_giveaway(chat: &Chat) -> (Uuid, Giveaway) {
+    let id = Uuid::new_v4();
+    let mut giveaway = Giveaway::new("Post".to_string(), vec![], user(USER_ID as u64), Lang::En);
+    giveaway.add_group_id(CHANNEL_ID.to_string());
+    giveaway.message_ref = Some(MessageRef {
+        chat_id: CHANNEL_ID,
+        message_id: 5,
+        published_at: 0,
+    });
+    giveaway.set_status(GiveawayStatus::Active).unwrap();
+
+    let giveaways = chat.db.giveaways();
+    giveaways
+        .create(USER_ID as u64, id, giveaway.clone())
+        .await
+        .unwrap();
+    giveaways.add_participant(id, &user(7)).await.unwrap();
+
+    (id, giveaway)
+}
+
+async fn announcements_due(chat: &Chat) -> usize {
+    let mut conn = chat.db.get().await.unwrap();
+    ScheduleStorage::new(ANNOUNCE_SCHEDULE_KEY.to_string(), &mut conn)
+        .range_to(i64::MAX)
+        .await
+        .unwrap()
+        .len()
+}
+
+#[tokio::test]
+async fn failed_channel_announcement_is_retried() {
+    let chat = Chat::new(Lang::En).await;
+    let (id, giveaway) = published_giveaway(&chat).await;
+    let owner = USER_ID as u64;
+
+    chat.fail("sendMessage", CHANNEL_ID);
+    chat.run(draw_and_announce(
+        chat.db.clone(),
+        chat.bot.clone(),
+        ChatId(USER_ID),
+        owner,
+        id,
+        giveaway,
+        1,
+        Lang::En,
+    ))
+    .await
+    .expect("Draw succeeds without the channel");
+
+    // The owner still gets the results
+    let to_owner = chat
+        .replies()
+        .await
+        .iter()
+        .filter(|reply| reply["chat_id"] == USER_ID)
+        .count();
+    assert!(to_owner > 0);
+
+    let mut ended = chat.db.giveaways().get(owner, id).await.unwrap().unwrap();
+    assert_eq!(ended.status(), GiveawayStatus::Ended);
+    assert_eq!(ended.get_winners()[0].id.0, 7);
+    assert!(ended.announcement_pending);
+    assert_eq!(announcements_due(&chat).await, 1);
+
+    chat.recover();
+    chat.run(post_announcement(
+        &chat.db, &chat.bot, owner, id, &mut ended,
+    ))
+    .await
+    .expect("Announcement is posted");
+
+    let to_channel = chat
+        .replies()
+        .await
+        .iter()
+        .filter(|reply| reply["chat_id"] == CHANNEL_ID)
+        .count();
+    assert_eq!(to_channel, 1);
+
+    let announced = chat.db.giveaways().get(owner, id).await.unwrap().unwrap();
+    assert!(!announced.announcement_pending);
+    assert_eq!(announcements_due(&chat).await, 0);
+}
+
+#[tokio::test]
+async fn draw_fails_when_subscriptions_cannot_be_checked() {
+    let chat = Chat::new(Lang::En).await;
+    let (id, giveaway) = published_giveaway(&chat).await;
+    let owner = USER_ID as u64;
+
+    chat.fail("getChatMember", CHANNEL_ID);
+    let drawn = chat
+        .run(draw_and_announce(
+            chat.db.clone(),
+            chat.bot.clone(),
+            ChatId(USER_ID),
+            owner,
+            id,
+            giveaway,
+            1,
+            Lang::En,
+        ))
+        .await;
+    assert!(drawn.is_err());
+
+    let kept = chat.db.giveaways().get(owner, id).await.unwrap().unwrap();
+    assert_eq!(kept.status(), GiveawayStatus::Active);
+    assert!(kept.get_winners().is_empty());
+    assert_eq!(announcements_due(&chat).await, 0);
+}
+
+#[tokio::test]
+async fn join_asks_to_try_again_when_subscriptions_cannot_be_checked() {
+    let chat = Chat::new(Lang::En).await;
+    let (id, _) = published_giveaway(&chat).await;
+
+    chat.fail("getChatMember", CHANNEL_ID);
+    chat.press_inline(&format!("p:{}", short_id(id)))
+        .await
+        .expect("Join is answered");
+
+    let alert = chat.calls("answerCallbackQuery").await.remove(0);
+    assert_eq!(
+        alert["text"],
+        Text::new("join.check_failed").render(Lang::En)
+    );
+    assert_eq!(chat.db.giveaways().participant_count(id).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn winner_of_a_draw_is_a_recent_winner() {
+    let chat = Chat::new(Lang::En).await;
+    let (id, giveaway) = published_giveaway(&chat).await;
+    let owner = USER_ID as u64;
+
+    chat.run(draw_and_announce(
+        chat.db.clone(),
+        chat.bot.clone(),
+        ChatId(USER_ID),
+        owner,
+        id,
+        giveaway.clone(),
+        1,
+        Lang::En,
+    ))
+    .await
+    .expect("Draw succeeds");
+
+    let rule = EligibilityRule::NoRecentWins { days: 30 };
+    for (user_id, rejected) in [(7, true), (8, false)] {
+        let ctx = JoinContext {
+            bot: &chat.bot,
+            pool: &chat.db,
+            giveaway: &giveaway,
+            user: &user(user_id),
+            participants: 0,
+        };
+        assert_eq!(rule.check(&ctx).await.unwrap().is_some(), rejected);
+    }
+}
+
+#[tokio::test]
+async fn participants_are_notified_about_cancellation_in_the_background() {
+    let chat = Chat::new(Lang::En).await;
+    let (id, _) = published_giveaway(&chat).await;
+
+    chat.press_inline(&format!("cn:{}", short_id(id)))
+        .await
+        .expect("Giveaway is cancelled");
+    assert!(chat.reply_texts().await.contains(&format!(
+        "{}{}",
+        Text::new("cancel.done").render(Lang::En),
+        Text::new("cancel.notifying").render(Lang::En)
+    )));
+
+    let report = Text::new("cancel.notified")
+        .arg("notified", 1)
+        .arg("total", 1)
+        .render(Lang::En);
+    for _ in 0..100 {
+        if chat.reply_texts().await.contains(&report) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let replies = chat.replies().await;
+    assert!(replies.iter().any(|reply| reply["chat_id"] == 7));
+    assert!(chat.reply_texts().await.contains(&report));
+}
+
+#[tokio::test]
+async fn concurrent_joins_do_not_overshoot_the_limit() {
+    let chat = Chat::new(Lang::En).await;
+    let (id, mut giveaway) = published_giveaway(&chat).await;
+    let owner = USER_ID as u64;
+
+    // One participant already, room for one more
+    giveaway.rules = vec![EligibilityRule::MaxParticipants { limit: 2 }];
+    chat.db
+        .giveaways()
+        .update(owner, id, giveaway)
+        .await
+        .unwrap();
+
+    let join = |user_id: u64| {
+        let q: CallbackQuery = serde_json::from_value(json!({
+            "id": user_id.to_string(),
+            "from": user(user_id),
+            "chat_instance": "1",
+            "data": format!("p:{}", short_id(id)),
+        }))
+        .expect("CallbackQuery");
+        write_participant(
+            chat.db.clone(),
+            chat.bot.clone(),
+            id,
+            owner,
+            user(user_id),
+            q,
+            Lang::En,
+        )
+    };
+
+    chat.run(async { tokio::try_join!(join(8), join(9)) })
+        .await
+        .expect("Both joins are answered");
+
+    assert_eq!(chat.db.giveaways().participant_count(id).await.unwrap(), 2);
+
+    let successes = chat
+        .calls("answerCallbackQuery")
+        .await
+        .iter()
+        .filter(|answer| answer["text"] == Text::new("join.success").render(Lang::En))
+        .count();
+    assert_eq!(successes, 1);
+}
+
+#[tokio::test]
+async fn owner_is_told_when_a_scheduled_end_cannot_happen() {
+    let chat = Chat::new(Lang::En).await;
+    let owner = USER_ID as u64;
+    let id = Uuid::new_v4();
+
+    let draft = Giveaway::new("Post".to_string(), vec![], user(owner), Lang::En);
+    chat.db.giveaways().create(owner, id, draft).await.unwrap();
+    {
+        let mut conn = chat.db.get().await.unwrap();
+        ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn)
+            .insert(ScheduledGiveaway { owner, id }, 0)
+            .await
+            .unwrap();
+    }
+
+    chat.run(end_due_giveaways(chat.bot.clone(), chat.db.clone()))
+        .await
+        .expect("Schedule is processed");
+
+    let notice = chat.replies().await.remove(0);
+    assert_eq!(notice["chat_id"], USER_ID);
+    assert!(notice["text"].as_str().unwrap().contains(&id.to_string()));
+
+    let mut conn = chat.db.get().await.unwrap();
+    let left = ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn)
+        .range_to(i64::MAX)
+        .await
+        .unwrap();
+    assert!(left.is_empty());
+}
@@ -0,0 +1,30 @@
+use super::Chat;
+use crate::i18n::{Lang, Text};
+use crate::models::{Command, State};
+
+#[test]
+fn placeholders_in_values_are_not_filled() {
+    let text = Text::new("cancel.ask")
+        .arg("id", "{text}")
+        .arg("text", "Win a {id}")
+        .render(Lang::En);
+    assert_eq!(text, "Cancel giveaway {text}?\n\nWin a {id}");
+}
+
+#[tokio::test]
+async fn help_lists_the_commands_in_the_user_language() {
+    for lang in Lang::ALL {
+        let chat = Chat::new(lang).await;
+        chat.set_state(State::Start).await;
+        chat.send("/help").await.expect("Help is sent");
+
+        let help = chat.reply_texts().await.remove(0);
+        for command in Command::localized(lang) {
+            let line = format!("/{} — {}", command.command, command.description);
+            assert!(help.contains(&line), "{line}");
+        }
+    }
+
+    let english = Command::localized(Lang::En);
+    assert!(english.iter().all(|command| command.description.is_ascii()));
+}
@@ -0,0 +1,216 @@
+use super::Chat;
+use crate::i18n::Lang;
+use crate::models::{ListCommands, MenuButton, MenuCommands, State, normalize_label};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// State and reply after pressing `menu` in the main menu of a user without giveaways
+fn expected(menu: MenuCommands) -> (fn(&State) -> bool, &'static str) {
+    match menu {
+        MenuCommands::CreateGiveaway => (|s| matches!(s, State::CreateGiveaway), "create.ask"),
+        MenuCommands::CancelGiveaway => (|s| matches!(s, State::StartedWindow), "cancel.none"),
+        MenuCommands::GiveawayList => (|s| matches!(s, State::StartedWindow), "list.empty"),
+        MenuCommands::AddGroupId => (|s| matches!(s, State::AddGroupId), "publish.ask"),
+        MenuCommands::SchedulePublication => {
+            (|s| matches!(s, State::SchedulePublication), "schedule.ask")
+        }
+        MenuCommands::ScheduledPublications => {
+            (|s| matches!(s, State::StartedWindow), "schedule.none")
+        }
+        MenuCommands::PartnerChannels => {
+            (|s| matches!(s, State::SetPartnerChannels), "partners.ask")
+        }
+        MenuCommands::EligibilityRules => (|s| matches!(s, State::SetRules), "rules.ask"),
+        MenuCommands::EndGiveaway => (|s| matches!(s, State::EndGiveaway), "end.ask"),
+        MenuCommands::GiveawayRoles => (|s| matches!(s, State::ChooseRole), "roles.ask"),
+        MenuCommands::SavedChannels => (|s| matches!(s, State::SavedChannels), "channels.none"),
+        MenuCommands::PauseGiveaway => (|s| matches!(s, State::StartedWindow), "pause.none"),
+        MenuCommands::ResumeGiveaway => (|s| matches!(s, State::StartedWindow), "resume.none"),
+        MenuCommands::EditGiveaway => (|s| matches!(s, State::StartedWindow), "edit.none"),
+    }
+}
+
+async fn press(chat: &Chat, from: State, label: &str) -> Option<State> {
+    chat.set_state(from).await;
+    chat.send(label).await.expect("Handler succeeds");
+    chat.state().await
+}
+
+#[test]
+fn labels_map_back_to_their_buttons() {
+    let mut labels = HashMap::new();
+
+    for lang in Lang::ALL {
+        for &menu in MenuCommands::ALL {
+            let label = menu.text().render(lang);
+            assert_eq!(MenuCommands::from_label(&label), Some(menu), "{label}");
+
+            if let Some(other) = labels.insert(normalize_label(&label), menu) {
+                assert_eq!(other, menu, "{label} is the label of two buttons");
+            }
+        }
+        for &button in ListCommands::ALL {
+            let label = button.text().render(lang);
+            assert_eq!(ListCommands::from_label(&label), Some(button), "{label}");
+        }
+    }
+}
+
+#[test]
+fn labels_ignore_case_emoji_and_spacing() {
+    assert_eq!(
+        MenuCommands::from_label("🎁  CREATE giveaway! "),
+        Some(MenuCommands::CreateGiveaway)
+    );
+    assert_eq!(
+        MenuCommands::from_label("📋 список розіграшів"),
+        Some(MenuCommands::GiveawayList)
+    );
+    assert_eq!(MenuCommands::from_label("🎁"), None);
+    assert_eq!(MenuCommands::from_label("Create"), None);
+}
+
+#[tokio::test]
+async fn every_menu_button_is_routed() {
+    for lang in Lang::ALL {
+        let chat = Chat::new(lang).await;
+
+        for &menu in MenuCommands::ALL {
+            let (is_expected, reply) = expected(menu);
+            let label = menu.text().render(lang);
+
+            let state = press(&chat, State::StartedWindow, &label).await;
+
+            assert!(state.as_ref().is_some_and(is_expected), "{}", menu.id());
+            assert!(chat.replied(reply).await, "{} replies {reply}", menu.id());
+        }
+    }
+}
+
+#[tokio::test]
+async fn buttons_of_other_languages_are_routed() {
+    let chat = Chat::new(Lang::Uk).await;
+
+    for &menu in MenuCommands::ALL {
+        let (is_expected, reply) = expected(menu);
+        let label = format!("✨ {}", menu.text().render(Lang::En).to_uppercase());
+
+        let state = press(&chat, State::StartedWindow, &label).await;
+
+        assert!(state.as_ref().is_some_and(is_expected), "{}", menu.id());
+        assert!(chat.replied(reply).await, "{} replies {reply}", menu.id());
+    }
+}
+
+#[tokio::test]
+async fn start_keyboard_buttons_are_all_routed() {
+    for lang in Lang::ALL {
+        let chat = Chat::new(lang).await;
+
+        chat.send("/start").await.expect("Start succeeds");
+        assert!(matches!(chat.state().await, Some(State::StartedWindow)));
+
+        let labels = chat.keyboard().await;
+        assert_eq!(labels.len(), MenuCommands::ALL.len());
+
+        for label in labels {
+            press(&chat, State::StartedWindow, &label).await;
+            assert!(!chat.replied("menu.unknown").await, "{label} is not routed");
+        }
+    }
+}
+
+#[tokio::test]
+async fn unknown_label_shows_the_menu_again() {
+    let chat = Chat::new(Lang::En).await;
+
+    for from in [
+        State::StartedWindow,
+        State::PickGiveaway {
+            channel: "@channel".to_string(),
+        },
+    ] {
+        let state = press(&chat, from, "Create a giveaway please").await;
+
+        assert!(matches!(state, Some(State::StartedWindow)));
+        assert!(chat.replied("menu.unknown").await);
+        assert_eq!(chat.keyboard().await.len(), MenuCommands::ALL.len());
+    }
+}
+
+#[tokio::test]
+async fn menu_is_routed_after_a_draw() {
+    let chat = Chat::new(Lang::Uk).await;
+
+    let label = MenuCommands::CreateGiveaway.text().render(Lang::Uk);
+    let state = press(&chat, State::RerollOrEnd, &label).await;
+    assert!(matches!(state, Some(State::CreateGiveaway)));
+
+    let state = press(&chat, State::RerollOrEnd, "щось інше").await;
+    assert!(matches!(state, Some(State::RerollOrEnd)));
+    assert!(chat.replied("end.reroll_or_finish").await);
+}
+
+#[tokio::test]
+async fn menu_is_routed_from_saved_channels() {
+    let chat = Chat::new(Lang::En).await;
+
+    let label = MenuCommands::GiveawayRoles.text().render(Lang::En);
+    let state = press(&chat, State::SavedChannels, &label).await;
+    assert!(matches!(state, Some(State::ChooseRole)));
+
+    let state = press(&chat, State::SavedChannels, "hello").await;
+    assert!(matches!(state, Some(State::StartedWindow)));
+    assert!(chat.replied("menu.unknown").await);
+}
+
+#[tokio::test]
+async fn list_buttons_are_routed() {
+    for lang in Lang::ALL {
+        let chat = Chat::new(lang).await;
+
+        let label = ListCommands::ShowParticipants.text().render(lang);
+        let state = press(&chat, State::List, &label).await;
+        assert!(matches!(state, Some(State::ShowParticipants)));
+
+        let label = ListCommands::Return.text().render(lang);
+        let state = press(&chat, State::List, &label).await;
+        assert!(matches!(state, Some(State::StartedWindow)));
+        assert!(chat.replied("list.returning").await);
+        assert_eq!(chat.keyboard().await.len(), MenuCommands::ALL.len());
+
+        let state = press(&chat, State::List, "?").await;
+        assert!(matches!(state, Some(State::List)));
+        assert!(chat.replied("menu.unknown").await);
+        assert_eq!(chat.keyboard().await.len(), ListCommands::ALL.len());
+    }
+}
+
+#[tokio::test]
+async fn menu_is_routed_from_every_input_step() {
+    let chat = Chat::new(Lang::En).await;
+    let label = MenuCommands::CreateGiveaway.text().render(Lang::En);
+    let id = Uuid::new_v4();
+
+    for from in [
+        State::CreateGiveaway,
+        State::CancelGiveaway,
+        State::AddGroupId,
+        State::EndGiveaway,
+        State::SetEndTime { id },
+        State::SchedulePublication,
+        State::SetPartnerChannels,
+        State::SetRules,
+        State::SetPrizes { id },
+        State::ChooseRole,
+        State::GrantRole { id, role: None },
+        State::PickChannel { id },
+        State::EditGiveaway,
+        State::EditContent { id },
+        State::ShowParticipants,
+    ] {
+        let state = press(&chat, from, &label).await;
+        assert!(matches!(state, Some(State::CreateGiveaway)));
+        assert!(chat.replied("create.ask").await);
+    }
+}
@@ -0,0 +1,338 @@
+mod conversations;
+mod draws;
+mod i18n;
+mod menu;
+mod redis_hash;
+mod repository;
+
+use crate::calls::models::Giveaway;
+use crate::db::Db;
+use crate::errors::AppResult;
+use crate::i18n::{Lang, Text};
+use crate::models::State;
+use crate::utils::schema;
+use serde_json::{Value, json};
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use teloxide::Bot;
+use teloxide::dispatching::dialogue::{ErasedStorage, InMemStorage, Storage};
+use teloxide::dptree;
+use teloxide::types::{ChatId, Me, Update, UpdateKind};
+use uuid::Uuid;
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+const USER_ID: i64 = 42;
+
+/// Answers every Bot API call: sent and edited messages are echoed back, everyone is
+/// a member of every chat, other methods return `true`. The `failing` calls to a chat fail
+struct TelegramApi {
+    failing: Vec<(String, i64)>,
+}
+
+impl Respond for TelegramApi {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let body = request.body_json::<Value>().unwrap_or_default();
+
+        // Channels are addressed by the id or @username stored as a string
+        let chat_id = body["chat_id"]
+            .as_i64()
+            .or_else(|| body["chat_id"].as_str()?.parse().ok());
+        if let Some(chat_id) = chat_id
+            && self.failing.contains(&(method(request), chat_id))
+        {
+            return ResponseTemplate::new(403).set_body_json(json!({
+                "ok": false,
+                "error_code": 403,
+                "description": "Forbidden: bot was kicked from the channel chat",
+            }));
+        }
+
+        let message = json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": body["chat_id"], "type": "private", "first_name": "Test"},
+            "text": body["text"].as_str().or(body["caption"].as_str()).unwrap_or_default(),
+        });
+
+        let result = match method(request).as_str() {
+            "sendmediagroup" => json!([message]),
+            "copymessage" => json!({"message_id": 1}),
+            "getchatmember" => json!({
+                "status": "member",
+                "user": {"id": body["user_id"], "is_bot": false, "first_name": "Member"},
+            }),
+            method if method.starts_with("send") || method.starts_with("edit") => message,
+            _ => json!(true),
+        };
+
+        ResponseTemplate::new(200).set_body_json(json!({"ok": true, "result": result}))
+    }
+}
+
+/// Bot API method of a request, in lowercase
+fn method(request: &Request) -> String {
+    request
+        .url
+        .path()
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// One user talking to the bot in a private chat, with a mock Bot API and in-memory storage
+pub struct Chat {
+    api: MockServer,
+    bot: Bot,
+    pub db: Db,
+    storage: Arc<ErasedStorage<State>>,
+    lang: Lang,
+    failing: std::sync::Mutex<Vec<(String, i64)>>,
+}
+
+impl Chat {
+    pub async fn new(lang: Lang) -> Self {
+        let api = MockServer::start().await;
+
+        let bot = Bot::new("1:token").set_api_url(api.uri().parse().expect("Mock server url"));
+
+        Chat {
+            api,
+            bot,
+            db: Db::memory(),
+            storage: InMemStorage::<State>::new().erase(),
+            lang,
+            failing: Default::default(),
+        }
+    }
+
+    pub async fn set_state(&self, state: State) {
+        self.storage
+            .clone()
+            .update_dialogue(ChatId(USER_ID), state)
+            .await
+            .expect("Dialogue is stored");
+    }
+
+    pub async fn state(&self) -> Option<State> {
+        self.storage
+            .clone()
+            .get_dialogue(ChatId(USER_ID))
+            .await
+            .expect("Dialogue is loaded")
+    }
+
+    fn user(&self) -> Value {
+        json!({
+            "id": USER_ID,
+            "is_bot": false,
+            "first_name": "Test",
+            "username": "tester",
+            "language_code": self.lang.code(),
+        })
+    }
+
+    /// Sends a text message from the user and runs it through the `schema`
+    pub async fn send(&self, text: &str) -> AppResult<()> {
+        self.send_message(json!({ "text": text })).await
+    }
+
+    /// Sends a photo with a caption, `group` makes it a part of an album
+    pub async fn send_photo(
+        &self,
+        file_id: &str,
+        caption: Option<&str>,
+        group: Option<&str>,
+    ) -> AppResult<()> {
+        self.send_message(json!({
+            "photo": [{"file_id": file_id, "file_unique_id": file_id, "width": 1, "height": 1}],
+            "caption": caption,
+            "media_group_id": group,
+        }))
+        .await
+    }
+
+    /// Sends a message with `fields` on top of the private chat and the user
+    pub async fn send_message(&self, fields: Value) -> AppResult<()> {
+        let mut message = json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": USER_ID, "type": "private", "first_name": "Test"},
+            "from": self.user(),
+        });
+        for (name, value) in fields.as_object().expect("Message fields") {
+            if !value.is_null() {
+                message[name] = value.clone();
+            }
+        }
+
+        self.dispatch(json!({ "update_id": 1, "message": message }))
+            .await
+    }
+
+    /// Presses an inline button with `data` under a message of the bot
+    pub async fn press_inline(&self, data: &str) -> AppResult<()> {
+        self.dispatch(json!({
+            "update_id": 1,
+            "callback_query": {
+                "id": "1",
+                "from": self.user(),
+                "chat_instance": "1",
+                "data": data,
+                "message": {
+                    "message_id": 1,
+                    "date": 0,
+                    "chat": {"id": USER_ID, "type": "private", "first_name": "Test"},
+                    "text": "",
+                },
+            },
+        }))
+        .await
+    }
+
+    /// Makes the calls of `method_name` to `chat_id` fail until `recover`
+    pub fn fail(&self, method_name: &str, chat_id: i64) {
+        self.failing
+            .lock()
+            .expect("Failing calls")
+            .push((method_name.to_lowercase(), chat_id));
+    }
+
+    pub fn recover(&self) {
+        self.failing.lock().expect("Failing calls").clear();
+    }
+
+    /// Runs a call of the bot outside of an update, like the scheduler does
+    pub async fn run<T>(&self, call: impl Future<Output = AppResult<T>>) -> AppResult<T> {
+        self.mock_api().await;
+        call.await
+    }
+
+    /// Only the requests made after this are kept
+    async fn mock_api(&self) {
+        self.api.reset().await;
+        let failing = self.failing.lock().expect("Failing calls").clone();
+        Mock::given(wiremock::matchers::any())
+            .respond_with(TelegramApi { failing })
+            .mount(&self.api)
+            .await;
+    }
+
+    async fn dispatch(&self, update: Value) -> AppResult<()> {
+        self.mock_api().await;
+
+        // Updates are only deserialized from text, like the ones from Telegram
+        let update = serde_json::from_str::<Update>(&update.to_string()).expect("Valid update");
+        assert!(
+            !matches!(update.kind, UpdateKind::Error(_)),
+            "Invalid update {update:?}"
+        );
+
+        let me = serde_json::from_value::<Me>(json!({
+            "id": 1,
+            "is_bot": true,
+            "first_name": "Bot",
+            "username": "test_bot",
+            "can_join_groups": false,
+            "can_read_all_group_messages": false,
+            "supports_inline_queries": false,
+            "can_connect_to_business": false,
+            "has_main_web_app": false,
+        }))
+        .expect("Valid bot user");
+
+        let deps = dptree::deps![
+            self.bot.clone(),
+            me,
+            update.clone(),
+            self.db.clone(),
+            self.storage.clone()
+        ];
+
+        match schema().dispatch(deps).await {
+            ControlFlow::Break(result) => result,
+            ControlFlow::Continue(_) => panic!("No handler for {update:?}"),
+        }
+    }
+
+    /// Bodies of the Bot API calls of `method` made for the last update
+    pub async fn calls(&self, method_name: &str) -> Vec<Value> {
+        self.api
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|request| method(request) == method_name.to_lowercase())
+            .map(|request| request.body_json::<Value>().expect("JSON body"))
+            .collect()
+    }
+
+    /// Giveaways created by the user
+    pub async fn giveaways(&self) -> Vec<(Uuid, Giveaway)> {
+        self.db
+            .giveaways()
+            .list(USER_ID as u64)
+            .await
+            .expect("Giveaways are stored")
+    }
+
+    /// Messages the bot sent for the last update
+    pub async fn replies(&self) -> Vec<Value> {
+        self.calls("sendMessage").await
+    }
+
+    pub async fn reply_texts(&self) -> Vec<String> {
+        self.replies()
+            .await
+            .iter()
+            .map(|reply| reply["text"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    /// Whether the bot answered the last update with the catalog text `key`
+    pub async fn replied(&self, key: &'static str) -> bool {
+        let text = Text::new(key).render(self.lang);
+        self.reply_texts().await.contains(&text)
+    }
+
+    /// Callback data of the inline keyboard of the last message that has one
+    pub async fn inline_buttons(&self) -> Vec<String> {
+        self.replies()
+            .await
+            .iter()
+            .rev()
+            .find_map(|reply| reply["reply_markup"]["inline_keyboard"].as_array().cloned())
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|row| row.as_array().cloned().unwrap_or_default())
+            .map(|button| {
+                button["callback_data"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    /// Whether a message sent for the last update hides the reply keyboard
+    pub async fn keyboard_removed(&self) -> bool {
+        self.replies()
+            .await
+            .iter()
+            .any(|reply| reply["reply_markup"]["remove_keyboard"] == Value::Bool(true))
+    }
+
+    /// Labels of the reply keyboard of the last message that has one
+    pub async fn keyboard(&self) -> Vec<String> {
+        self.replies()
+            .await
+            .iter()
+            .rev()
+            .find_map(|reply| reply["reply_markup"]["keyboard"].as_array().cloned())
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|row| row.as_array().cloned().unwrap_or_default())
+            .map(|button| button["text"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+}
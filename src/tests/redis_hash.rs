@@ -0,0 +1,215 @@
+use crate::calls::types::RHashMap;
+use crate::db::{Db, DbConnection};
+use bb8_redis::RedisConnectionManager;
+use bb8_redis::bb8::Pool;
+use std::time::Duration;
+use uuid::Uuid;
+
+type Names<'a> = RHashMap<'a, DbConnection, String, u64, String>;
+type Counters<'a> = RHashMap<'a, DbConnection, String, String, i64>;
+
+/// Every check runs against the in-memory database, and against the Redis server
+/// at `REDIS_URL` or on localhost with `cargo test -- --ignored`
+macro_rules! hash_tests {
+    ($($check:ident),* $(,)?) => {
+        mod in_memory {
+            $(
+                #[tokio::test]
+                async fn $check() {
+                    super::$check(crate::db::Db::memory()).await;
+                }
+            )*
+        }
+
+        mod in_redis {
+            $(
+                #[tokio::test]
+                #[ignore = "needs a Redis server"]
+                async fn $check() {
+                    super::$check(super::redis_db().await).await;
+                }
+            )*
+        }
+    };
+}
+
+hash_tests!(
+    get_many_len_and_contains,
+    incr_starts_from_zero,
+    expire_and_persist,
+    scan_returns_every_field_once,
+    compare_and_set_checks_the_current_value,
+    watched_write_is_aborted_by_a_concurrent_one,
+);
+
+async fn redis_db() -> Db {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+    let manager = RedisConnectionManager::new(url.clone()).expect("Invalid REDIS_URL");
+    let pool = Pool::builder()
+        .connection_timeout(Duration::from_secs(1))
+        .build(manager)
+        .await
+        .expect("Redis pool is built");
+
+    let db = Db::redis(pool);
+    if let Err(e) = db.get().await {
+        panic!("No Redis server at {url}: {e}");
+    }
+    db
+}
+
+fn test_key() -> String {
+    format!("test:{}", Uuid::new_v4())
+}
+
+async fn get_many_len_and_contains(db: Db) {
+    let mut conn = db.get().await.unwrap();
+    let mut names = Names::new(test_key(), &mut conn);
+
+    assert_eq!(names.len().await.unwrap(), 0);
+    assert_eq!(names.get_many(&[1]).await.unwrap(), [None]);
+
+    names.insert(1, "one".to_string(), None).await.unwrap();
+    names.insert(3, "three".to_string(), None).await.unwrap();
+
+    assert_eq!(names.len().await.unwrap(), 2);
+    assert!(names.contains(3).await.unwrap());
+    assert!(!names.contains(2).await.unwrap());
+    assert_eq!(
+        names.get_many(&[3, 2, 1]).await.unwrap(),
+        [Some("three".to_string()), None, Some("one".to_string())]
+    );
+    assert!(names.get_many(&[]).await.unwrap().is_empty());
+
+    names.clear().await.unwrap();
+}
+
+async fn incr_starts_from_zero(db: Db) {
+    let mut conn = db.get().await.unwrap();
+    let mut counters = Counters::new(test_key(), &mut conn);
+
+    assert_eq!(counters.incr("joins".to_string(), 2).await.unwrap(), 2);
+    assert_eq!(counters.incr("joins".to_string(), -5).await.unwrap(), -3);
+    assert_eq!(counters.get("joins".to_string()).await.unwrap(), Some(-3));
+
+    counters
+        .insert("views".to_string(), 10, None)
+        .await
+        .unwrap();
+    assert_eq!(counters.incr("views".to_string(), 1).await.unwrap(), 11);
+
+    counters.clear().await.unwrap();
+}
+
+async fn expire_and_persist(db: Db) {
+    let mut conn = db.get().await.unwrap();
+    let mut names = Names::new(test_key(), &mut conn);
+    let ttl = Duration::from_millis(100);
+
+    assert!(!names.expire(1, ttl).await.unwrap());
+    assert!(!names.persist(1).await.unwrap());
+
+    names.insert(1, "kept".to_string(), None).await.unwrap();
+    names.insert(2, "gone".to_string(), None).await.unwrap();
+    assert!(!names.persist(1).await.unwrap());
+
+    assert!(names.expire(1, ttl).await.unwrap());
+    assert!(names.expire(2, ttl).await.unwrap());
+    assert!(names.persist(1).await.unwrap());
+
+    tokio::time::sleep(ttl * 3).await;
+
+    assert_eq!(names.get(1).await.unwrap(), Some("kept".to_string()));
+    assert!(!names.contains(2).await.unwrap());
+    assert_eq!(names.len().await.unwrap(), 1);
+
+    names.clear().await.unwrap();
+}
+
+async fn scan_returns_every_field_once(db: Db) {
+    let mut conn = db.get().await.unwrap();
+    let mut names = Names::new(test_key(), &mut conn);
+
+    assert!(names.scan(10).next().await.unwrap().is_none());
+
+    for id in 0..25 {
+        names.insert(id, format!("user {id}"), None).await.unwrap();
+    }
+
+    let mut scanned = vec![];
+    let mut scan = names.scan(7);
+    while let Some((id, name)) = scan.next().await.unwrap() {
+        assert_eq!(name, format!("user {id}"));
+        scanned.push(id);
+    }
+    scanned.sort();
+    assert_eq!(scanned, (0..25).collect::<Vec<_>>());
+
+    names.clear().await.unwrap();
+}
+
+async fn compare_and_set_checks_the_current_value(db: Db) {
+    let mut conn = db.get().await.unwrap();
+    let mut names = Names::new(test_key(), &mut conn);
+
+    assert!(
+        names
+            .compare_and_set(1, None, "first".to_string())
+            .await
+            .unwrap()
+    );
+    assert!(
+        !names
+            .compare_and_set(1, None, "second".to_string())
+            .await
+            .unwrap()
+    );
+    assert!(
+        !names
+            .compare_and_set(1, Some("other".to_string()), "second".to_string())
+            .await
+            .unwrap()
+    );
+    assert_eq!(names.get(1).await.unwrap(), Some("first".to_string()));
+
+    assert!(
+        names
+            .compare_and_set(1, Some("first".to_string()), "second".to_string())
+            .await
+            .unwrap()
+    );
+    assert_eq!(names.get(1).await.unwrap(), Some("second".to_string()));
+
+    names.clear().await.unwrap();
+}
+
+/// The transaction `compare_and_set` relies on is dropped if another connection
+/// writes to the watched hash
+async fn watched_write_is_aborted_by_a_concurrent_one(db: Db) {
+    let key = test_key();
+    let mut conn = db.get().await.unwrap();
+    let mut other = db.get().await.unwrap();
+
+    redis::cmd("WATCH")
+        .arg(&key)
+        .query_async::<()>(&mut conn)
+        .await
+        .unwrap();
+    Names::new(key.clone(), &mut other)
+        .insert(1, "other".to_string(), None)
+        .await
+        .unwrap();
+
+    let written: Option<(i64,)> = redis::pipe()
+        .atomic()
+        .hset(&key, "1", "\"mine\"")
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+    assert!(written.is_none());
+
+    let mut names = Names::new(key, &mut conn);
+    assert_eq!(names.get(1).await.unwrap(), Some("other".to_string()));
+
+    names.clear().await.unwrap();
+}
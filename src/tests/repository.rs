@@ -0,0 +1,251 @@
+use crate::calls::models::{
+    Giveaway, PendingAlbums, ScheduleStorage, ScheduledGiveaway, UserLanguages,
+};
+use crate::consts::{END_SCHEDULE_KEY, PENDING_ALBUMS_KEY, USER_LANGUAGES_KEY};
+use crate::db::Db;
+use crate::db::giveaways::GiveawayRepository;
+use crate::db::sqlite;
+use crate::i18n::Lang;
+use std::path::PathBuf;
+use std::time::Duration;
+use teloxide::types::{User, UserId};
+use uuid::Uuid;
+
+fn user(id: u64, name: &str) -> User {
+    serde_json::from_value(serde_json::json!({
+        "id": id,
+        "is_bot": false,
+        "first_name": name,
+    }))
+    .expect("User")
+}
+
+/// SQLite file removed with its journal when the test ends
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new() -> Self {
+        TempFile(std::env::temp_dir().join(format!("giveaways-{}.sqlite", Uuid::new_v4())))
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().expect("UTF-8 temp path")
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", self.path()));
+        }
+    }
+}
+
+/// The same expectations for every backend
+async fn check(giveaways: &dyn GiveawayRepository) {
+    let owner = user(1, "Owner");
+    let id = Uuid::new_v4();
+    let other = Uuid::new_v4();
+
+    assert!(giveaways.get(1, id).await.unwrap().is_none());
+    assert!(giveaways.owner(id).await.unwrap().is_none());
+    assert!(giveaways.list(1).await.unwrap().is_empty());
+
+    let giveaway = Giveaway::new("First".to_string(), vec![], owner.clone(), Lang::En);
+    giveaways.create(1, id, giveaway.clone()).await.unwrap();
+    giveaways.create(2, other, giveaway).await.unwrap();
+
+    assert_eq!(giveaways.owner(id).await.unwrap(), Some(1));
+    assert_eq!(giveaways.owner(other).await.unwrap(), Some(2));
+    assert!(giveaways.get(2, id).await.unwrap().is_none());
+
+    let mut giveaway = giveaways.get(1, id).await.unwrap().expect("Created");
+    assert_eq!(giveaway.get_text(), "First");
+
+    giveaway.text = "Edited".to_string();
+    giveaways.update(1, id, giveaway).await.unwrap();
+
+    let list = giveaways.list(1).await.unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].0, id);
+    assert_eq!(list[0].1.get_text(), "Edited");
+
+    assert_eq!(
+        giveaways.add_participant(id, &user(30, "C")).await.unwrap(),
+        Some(1)
+    );
+    assert_eq!(
+        giveaways.add_participant(id, &user(10, "A")).await.unwrap(),
+        Some(2)
+    );
+    assert_eq!(
+        giveaways
+            .add_participant(id, &user(10, "A2"))
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        giveaways
+            .add_participant(other, &user(20, "B"))
+            .await
+            .unwrap(),
+        Some(1)
+    );
+
+    assert_eq!(giveaways.participant_count(id).await.unwrap(), 2);
+    assert!(giveaways.is_participant(id, UserId(30)).await.unwrap());
+    assert!(!giveaways.is_participant(id, UserId(20)).await.unwrap());
+
+    let renamed = giveaways
+        .participant(id, 10)
+        .await
+        .unwrap()
+        .expect("Joined");
+    assert_eq!(renamed.first_name, "A2");
+    assert!(giveaways.participant(id, 20).await.unwrap().is_none());
+
+    let ids = giveaways
+        .participants(id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|user| user.id.0)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [10, 30]);
+
+    giveaways
+        .remove_participant(other, UserId(20))
+        .await
+        .unwrap();
+    assert_eq!(giveaways.participant_count(other).await.unwrap(), 0);
+    assert!(giveaways.participant(other, 20).await.unwrap().is_none());
+    assert_eq!(
+        giveaways
+            .add_participant(other, &user(20, "B"))
+            .await
+            .unwrap(),
+        Some(1)
+    );
+}
+
+#[tokio::test]
+async fn memory_repository() {
+    check(Db::memory().giveaways()).await;
+}
+
+#[tokio::test]
+async fn redis_repository() {
+    check(Db::redis_in_memory().giveaways()).await;
+}
+
+#[tokio::test]
+async fn sqlite_repository() {
+    let file = TempFile::new();
+    let db = Db::sqlite(sqlite::open(file.path()).await.unwrap())
+        .await
+        .unwrap();
+
+    check(db.giveaways()).await;
+}
+
+#[tokio::test]
+async fn sqlite_data_survives_a_restart() {
+    let file = TempFile::new();
+    let id = Uuid::new_v4();
+
+    {
+        let db = Db::sqlite(sqlite::open(file.path()).await.unwrap())
+            .await
+            .unwrap();
+        let giveaway = Giveaway::new("Kept".to_string(), vec![], user(1, "Owner"), Lang::Uk);
+        db.giveaways().create(1, id, giveaway).await.unwrap();
+
+        let mut conn = db.get().await.unwrap();
+        UserLanguages::new(USER_LANGUAGES_KEY.to_string(), &mut conn)
+            .insert(1, Lang::En, None)
+            .await
+            .unwrap();
+        ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn)
+            .insert(ScheduledGiveaway { owner: 1, id }, 100)
+            .await
+            .unwrap();
+    }
+
+    let db = Db::sqlite(sqlite::open(file.path()).await.unwrap())
+        .await
+        .unwrap();
+
+    let giveaway = db.giveaways().get(1, id).await.unwrap().expect("Kept");
+    assert_eq!(giveaway.get_text(), "Kept");
+
+    let mut conn = db.get().await.unwrap();
+    let lang = UserLanguages::new(USER_LANGUAGES_KEY.to_string(), &mut conn)
+        .get(1)
+        .await
+        .unwrap();
+    assert_eq!(lang, Some(Lang::En));
+
+    let scheduled = ScheduleStorage::new(END_SCHEDULE_KEY.to_string(), &mut conn)
+        .range_to(i64::MAX)
+        .await
+        .unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].0.id, id);
+    assert_eq!(scheduled[0].1, 100);
+}
+
+#[tokio::test]
+async fn sqlite_drops_expired_fields_on_write() {
+    let file = TempFile::new();
+    let pool = sqlite::open(file.path()).await.unwrap();
+    let db = Db::sqlite(pool.clone()).await.unwrap();
+    let mut conn = db.get().await.unwrap();
+    let mut albums = PendingAlbums::new(PENDING_ALBUMS_KEY.to_string(), &mut conn);
+
+    albums
+        .insert(
+            "old".to_string(),
+            Uuid::new_v4(),
+            Some(Duration::from_millis(50)),
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    albums
+        .insert(
+            "new".to_string(),
+            Uuid::new_v4(),
+            Some(Duration::from_secs(60)),
+        )
+        .await
+        .unwrap();
+
+    let rows: Vec<(Vec<u8>,)> = sqlx::query_as("SELECT item FROM kv_items WHERE key = ?")
+        .bind(PENDING_ALBUMS_KEY.as_bytes())
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(rows, [(b"\"new\"".to_vec(),)]);
+}
+
+#[tokio::test]
+async fn sqlite_write_that_cannot_be_saved_is_undone() {
+    let file = TempFile::new();
+    let pool = sqlite::open(file.path()).await.unwrap();
+    let db = Db::sqlite(pool.clone()).await.unwrap();
+    let mut conn = db.get().await.unwrap();
+    let mut languages = UserLanguages::new(USER_LANGUAGES_KEY.to_string(), &mut conn);
+
+    languages.insert(1, Lang::En, None).await.unwrap();
+
+    sqlx::query("DROP TABLE kv_items")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert!(languages.insert(1, Lang::Uk, None).await.is_err());
+    assert!(languages.insert(2, Lang::Uk, None).await.is_err());
+
+    assert_eq!(languages.get(1).await.unwrap(), Some(Lang::En));
+    assert_eq!(languages.get(2).await.unwrap(), None);
+}
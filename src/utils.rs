@@ -1,10 +1,21 @@
-use crate::calls::basic_methods::{cancel, help, invalid_state, start};
+use crate::calls::basic_methods::{cancel, help, invalid_state, language, start};
 use crate::calls::giveaway_methods::{
-    add_group_id, cancel_giveaway, create_giveaway, handle_callback_from_button, list,
-    show_participants, started_window,
+    add_album_item, add_group_id, archive, cancel_giveaway, choose_role, create_giveaway,
+    edit_content, edit_giveaway, end_giveaway, grant_role, handle_callback_from_button, list,
+    pending_album, pick_channel, post_language, reroll_or_end, save_channel_membership,
+    save_forwarded_channel, schedule_publication, set_end_time, set_partner_channels, set_prizes,
+    set_rules, show_participants, started_window, unknown_list_button, unknown_menu_button, verify,
 };
+use crate::calls::language::update_lang;
+use crate::calls::models::Giveaway;
+use crate::calls::status::GiveawayStatus;
+use crate::consts::{DATE_FORMAT, GIVEAWAYS_PAGE_SIZE, KEYBOARD_ROW_SIZE};
 use crate::errors::AppResult;
-use crate::models::{Command, State};
+use crate::i18n::Lang;
+use crate::models::{
+    Command, GiveawayAction, ListCommands, MenuButton, MenuCommands, State, pressed_button,
+};
+use chrono::NaiveDateTime;
 use colored::*;
 use log::{Level, LevelFilter};
 use std::collections::HashMap;
@@ -15,7 +26,11 @@ use teloxide::dispatching::{DpHandlerDescription, UpdateFilterExt, dialogue};
 use teloxide::dptree;
 use teloxide::dptree::{Handler, case};
 use teloxide::prelude::{DependencyMap, Message, Update};
-use teloxide::types::{ChatKind, KeyboardButton, KeyboardMarkup};
+use teloxide::types::{
+    ChatKind, InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup, User,
+};
+use teloxide::utils::html;
+use uuid::Uuid;
 
 pub fn schema() -> Handler<'static, DependencyMap, AppResult<()>, DpHandlerDescription> {
     let command_handler = teloxide::filter_command::<Command, _>()
@@ -25,34 +40,93 @@ pub fn schema() -> Handler<'static, DependencyMap, AppResult<()>, DpHandlerDescr
                 .branch(case![Command::Help].endpoint(help))
                 .branch(case![Command::Start(start)].endpoint(start)),
         )
-        .branch(case![Command::Cancel].endpoint(cancel));
+        .branch(case![Command::Cancel].endpoint(cancel))
+        .branch(case![Command::Verify(id)].endpoint(verify))
+        .branch(case![Command::Archive(id)].endpoint(archive))
+        .branch(case![Command::Language(code)].endpoint(language))
+        .branch(case![Command::Postlang(args)].endpoint(post_language));
+
+    // Buttons are routed by their id, a label that is not in the registry falls through to
+    // the handlers of unknown buttons below. Steps that take free text check the menu first,
+    // so a button of a keyboard still on screen is not saved as input
+    let menu_handler = dptree::filter_map(pressed_button::<MenuCommands>)
+        .branch(case![State::StartedWindow].endpoint(started_window))
+        .branch(case![State::CreateGiveaway].endpoint(started_window))
+        .branch(case![State::CancelGiveaway].endpoint(started_window))
+        .branch(case![State::AddGroupId].endpoint(started_window))
+        .branch(case![State::EndGiveaway].endpoint(started_window))
+        .branch(case![State::RerollOrEnd].endpoint(started_window))
+        .branch(case![State::SetEndTime { id }].endpoint(started_window))
+        .branch(case![State::SchedulePublication].endpoint(started_window))
+        .branch(case![State::SetPartnerChannels].endpoint(started_window))
+        .branch(case![State::SetRules].endpoint(started_window))
+        .branch(case![State::SetPrizes { id }].endpoint(started_window))
+        .branch(case![State::ChooseRole].endpoint(started_window))
+        .branch(case![State::GrantRole { id, role }].endpoint(started_window))
+        .branch(case![State::PickChannel { id }].endpoint(started_window))
+        .branch(case![State::PickGiveaway { channel }].endpoint(started_window))
+        .branch(case![State::EditGiveaway].endpoint(started_window))
+        .branch(case![State::EditContent { id }].endpoint(started_window))
+        .branch(case![State::ShowParticipants].endpoint(started_window));
+
+    let list_handler = dptree::filter_map(pressed_button::<ListCommands>)
+        .branch(case![State::List].endpoint(list));
 
     let subcommand_handler = Update::filter_message()
         .filter(|msg: Message| matches!(msg.chat.kind, ChatKind::Private(_)))
-        .branch(case![State::StartedWindow].endpoint(started_window))
+        .branch(menu_handler)
+        .branch(list_handler)
+        .branch(case![State::StartedWindow].endpoint(unknown_menu_button))
         .branch(case![State::CreateGiveaway].endpoint(create_giveaway))
         .branch(case![State::CancelGiveaway].endpoint(cancel_giveaway))
         .branch(case![State::AddGroupId].endpoint(add_group_id))
-        .branch(case![State::List].endpoint(list))
+        .branch(case![State::EndGiveaway].endpoint(end_giveaway))
+        .branch(case![State::RerollOrEnd].endpoint(reroll_or_end))
+        .branch(case![State::SetEndTime { id }].endpoint(set_end_time))
+        .branch(case![State::SchedulePublication].endpoint(schedule_publication))
+        .branch(case![State::SetPartnerChannels].endpoint(set_partner_channels))
+        .branch(case![State::SetRules].endpoint(set_rules))
+        .branch(case![State::SetPrizes { id }].endpoint(set_prizes))
+        .branch(case![State::ChooseRole].endpoint(choose_role))
+        .branch(case![State::GrantRole { id, role }].endpoint(grant_role))
+        .branch(case![State::PickChannel { id }].endpoint(pick_channel))
+        .branch(case![State::SavedChannels].endpoint(save_forwarded_channel))
+        .branch(case![State::PickGiveaway { channel }].endpoint(unknown_menu_button))
+        .branch(case![State::EditGiveaway].endpoint(edit_giveaway))
+        .branch(case![State::EditContent { id }].endpoint(edit_content))
+        .branch(case![State::List].endpoint(unknown_list_button))
         .branch(case![State::ShowParticipants].endpoint(show_participants));
 
     let callback_handler = Update::filter_callback_query().endpoint(handle_callback_from_button);
 
+    // Files of an album after the first one, they arrive as separate messages
+    let album_handler = Update::filter_message()
+        .filter_map_async(pending_album)
+        .endpoint(add_album_item);
+
     let message_handler = Update::filter_message()
         .filter(|msg: Message| matches!(msg.chat.kind, ChatKind::Private(_)))
+        .branch(album_handler)
         .branch(command_handler)
         .branch(subcommand_handler)
         .branch(dptree::endpoint(invalid_state));
 
-    dialogue::enter::<Update, ErasedStorage<State>, State, _>()
-        .branch(callback_handler)
-        .branch(message_handler)
+    let my_chat_member_handler = Update::filter_my_chat_member().endpoint(save_channel_membership);
+
+    dptree::entry()
+        .map_async(update_lang)
+        .branch(my_chat_member_handler)
+        .branch(
+            dialogue::enter::<Update, ErasedStorage<State>, State, _>()
+                .branch(callback_handler)
+                .branch(message_handler),
+        )
 }
 
 pub fn make_keyboard(menu_buttons: Vec<String>) -> KeyboardMarkup {
     let mut keyboard: Vec<Vec<KeyboardButton>> = vec![];
 
-    for menu_button in menu_buttons.chunks(menu_buttons.len()) {
+    for menu_button in menu_buttons.chunks(KEYBOARD_ROW_SIZE) {
         let row = menu_button
             .iter()
             .map(|version| KeyboardButton::new(version.to_owned()))
@@ -64,6 +138,132 @@ pub fn make_keyboard(menu_buttons: Vec<String>) -> KeyboardMarkup {
     KeyboardMarkup::new(keyboard)
 }
 
+/// Reply keyboard with the labels of `B` buttons in `lang`
+pub fn buttons_keyboard<B: MenuButton>(lang: Lang) -> KeyboardMarkup {
+    make_keyboard(
+        B::ALL
+            .iter()
+            .map(|button| button.text().render(lang))
+            .collect(),
+    )
+}
+
+pub fn main_menu_keyboard(lang: Lang) -> KeyboardMarkup {
+    buttons_keyboard::<MenuCommands>(lang)
+}
+
+pub fn list_keyboard(lang: Lang) -> KeyboardMarkup {
+    buttons_keyboard::<ListCommands>(lang)
+}
+
+/// Label of a giveaway in inline keyboards: the start of its text and its status
+pub fn giveaway_button_text(giveaway: &Giveaway, lang: Lang) -> String {
+    let text = giveaway.get_text().lines().next().unwrap_or_default();
+    let mut label = text.chars().take(30).collect::<String>();
+    if text.chars().count() > 30 {
+        label.push('…');
+    }
+    format!("{label} · {}", giveaway.status().text().render(lang))
+}
+
+/// One page of giveaways to pick for `action`, with buttons to the neighbouring pages
+pub fn giveaways_keyboard(
+    giveaways: &[(Uuid, Giveaway)],
+    action: GiveawayAction,
+    page: usize,
+    lang: Lang,
+) -> InlineKeyboardMarkup {
+    let pages = giveaways.len().div_ceil(GIVEAWAYS_PAGE_SIZE);
+    let page = page.min(pages.saturating_sub(1));
+
+    let mut keyboard = giveaways
+        .iter()
+        .skip(page * GIVEAWAYS_PAGE_SIZE)
+        .take(GIVEAWAYS_PAGE_SIZE)
+        .map(|(id, giveaway)| {
+            vec![InlineKeyboardButton::callback(
+                giveaway_button_text(giveaway, lang),
+                format!("gs:{}:{}", action.code(), short_id(*id)),
+            )]
+        })
+        .collect::<Vec<_>>();
+
+    let mut navigation = vec![];
+    if page > 0 {
+        navigation.push(InlineKeyboardButton::callback(
+            "◀️",
+            format!("gn:{}:{}", action.code(), page - 1),
+        ));
+    }
+    if page + 1 < pages {
+        navigation.push(InlineKeyboardButton::callback(
+            "▶️",
+            format!("gn:{}:{}", action.code(), page + 1),
+        ));
+    }
+    if !navigation.is_empty() {
+        keyboard.push(navigation);
+    }
+
+    InlineKeyboardMarkup::new(keyboard)
+}
+
+/// Buttons that list the giveaways with one status, the archive is only reachable from here
+pub fn status_filter_keyboard(lang: Lang) -> InlineKeyboardMarkup {
+    let buttons = GiveawayStatus::ALL
+        .into_iter()
+        .map(|status| {
+            InlineKeyboardButton::callback(
+                status.group_title().render(lang),
+                format!("gf:{}", status.code()),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    InlineKeyboardMarkup::new(buttons.chunks(KEYBOARD_ROW_SIZE).map(|row| row.to_vec()))
+}
+
+pub fn mention(user: &User) -> String {
+    let name = user
+        .username
+        .clone()
+        .unwrap_or_else(|| user.first_name.clone());
+    html::user_mention(user.id, &name)
+}
+
+/// Short id of a giveaway for callback data, Telegram limits it to 64 bytes
+pub fn short_id(uuid: Uuid) -> String {
+    let mut value = uuid.as_u128();
+    let mut digits = vec![];
+
+    loop {
+        digits.push(char::from_digit((value % 36) as u32, 36).expect("Digit is below radix"));
+        value /= 36;
+        if value == 0 {
+            break;
+        }
+    }
+
+    digits.iter().rev().collect()
+}
+
+pub fn parse_short_id(id: &str) -> Option<Uuid> {
+    u128::from_str_radix(id, 36).ok().map(Uuid::from_u128)
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|date| date.format(DATE_FORMAT).to_string())
+        .unwrap_or_default()
+}
+
+/// Parses a date in `DATE_FORMAT` as UTC into a unix timestamp
+pub fn parse_timestamp(date: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(date, DATE_FORMAT)
+        .ok()
+        .map(|date| date.and_utc().timestamp())
+}
+
 pub fn init_logging() -> AppResult<()> {
     // Logging lib errors and all app logs
     let log_level = LevelFilter::Debug;